@@ -0,0 +1,228 @@
+//! CalDAV (RFC 4791) calendar source.
+//!
+//! Events are requested with a `calendar-query` REPORT limited to the agenda
+//! time range, and the returned `calendar-data` is handed to the same
+//! iCalendar reader used for plain `.ics` files.
+
+use crate::{config::CalDavSource, HttpsClient};
+use chrono::{DateTime, Utc};
+use hyper::{header, Body, Method, Request, StatusCode};
+use quick_xml::{events::Event as XmlEvent, Reader};
+
+fn query(from: DateTime<Utc>, until: DateTime<Utc>) -> String {
+    format!(
+        r#"<?xml version="1.0" encoding="utf-8"?>
+<C:calendar-query xmlns:D="DAV:" xmlns:C="urn:ietf:params:xml:ns:caldav">
+  <D:prop>
+    <C:calendar-data/>
+  </D:prop>
+  <C:filter>
+    <C:comp-filter name="VCALENDAR">
+      <C:comp-filter name="VEVENT">
+        <C:time-range start="{}" end="{}"/>
+      </C:comp-filter>
+    </C:comp-filter>
+  </C:filter>
+</C:calendar-query>"#,
+        from.format("%Y%m%dT%H%M%SZ"),
+        until.format("%Y%m%dT%H%M%SZ")
+    )
+}
+
+/// Pulls the text of every `calendar-data` element out of a multistatus
+/// response, whatever namespace prefix the server picked.
+fn calendar_data(xml: &str) -> Result<Vec<String>, Box<dyn std::error::Error + Send + Sync>> {
+    let mut reader = Reader::from_str(xml);
+    let mut buf = Vec::new();
+    let mut inside = false;
+    let mut current = String::new();
+    let mut found = Vec::new();
+    loop {
+        match reader.read_event(&mut buf)? {
+            XmlEvent::Start(e) if e.local_name() == b"calendar-data" => {
+                inside = true;
+                current.clear();
+            }
+            XmlEvent::End(e) if e.local_name() == b"calendar-data" => {
+                inside = false;
+                found.push(current.clone());
+            }
+            XmlEvent::Text(e) if inside => current.push_str(&e.unescape_and_decode(&reader)?),
+            XmlEvent::CData(e) if inside => current.push_str(&String::from_utf8_lossy(&e)),
+            XmlEvent::Eof => break,
+            _ => {}
+        }
+        buf.clear();
+    }
+    Ok(found)
+}
+
+/// Runs a time-range REPORT against a CalDAV collection and returns the raw
+/// iCalendar objects it matched.
+pub async fn fetch(
+    client: &HttpsClient,
+    source: &CalDavSource,
+    from: DateTime<Utc>,
+    until: DateTime<Utc>,
+) -> Result<Vec<String>, Box<dyn std::error::Error + Send + Sync>> {
+    let mut req = Request::builder()
+        .method(Method::from_bytes(b"REPORT")?)
+        .uri(source.url.as_str())
+        .header("Depth", "1")
+        .header(header::CONTENT_TYPE, "application/xml; charset=utf-8");
+    if let Some(username) = &source.username {
+        let credentials = format!(
+            "{}:{}",
+            username,
+            source.password.as_deref().unwrap_or_default()
+        );
+        req = req.header(
+            header::AUTHORIZATION,
+            format!("Basic {}", base64::encode(credentials)),
+        );
+    }
+    let resp = client
+        .request(req.body(Body::from(query(from, until)))?)
+        .await?;
+    let status = resp.status();
+    if status != StatusCode::MULTI_STATUS && !status.is_success() {
+        return Err(format!("{} returned {}", source.url, status).into());
+    }
+    let body = hyper::body::to_bytes(resp.into_body()).await?;
+    calendar_data(&String::from_utf8_lossy(&body))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{config::LocaleConfig, locale::Locale};
+    use chrono::TimeZone;
+    use hyper::{
+        service::{make_service_fn, service_fn},
+        Response, Server,
+    };
+    use hyper_tls::HttpsConnector;
+    use std::{convert::Infallible, net::SocketAddr};
+
+    const MULTISTATUS: &str = r#"<?xml version="1.0" encoding="utf-8"?>
+<d:multistatus xmlns:d="DAV:" xmlns:cal="urn:ietf:params:xml:ns:caldav">
+  <d:response>
+    <d:href>/cal/team/standup.ics</d:href>
+    <d:propstat>
+      <d:prop>
+        <cal:calendar-data>BEGIN:VCALENDAR
+BEGIN:VEVENT
+UID:standup
+SUMMARY:Standup &amp; coffee
+DTSTART:20200302T150000Z
+DTEND:20200302T151500Z
+END:VEVENT
+END:VCALENDAR
+</cal:calendar-data>
+      </d:prop>
+      <d:status>HTTP/1.1 200 OK</d:status>
+    </d:propstat>
+  </d:response>
+  <d:response>
+    <d:href>/cal/team/review.ics</d:href>
+    <d:propstat>
+      <d:prop>
+        <cal:calendar-data><![CDATA[BEGIN:VCALENDAR
+BEGIN:VEVENT
+UID:review
+SUMMARY:Review <draft>
+DTSTART:20200302T170000Z
+DTEND:20200302T180000Z
+END:VEVENT
+END:VCALENDAR
+]]></cal:calendar-data>
+      </d:prop>
+      <d:status>HTTP/1.1 200 OK</d:status>
+    </d:propstat>
+  </d:response>
+</d:multistatus>"#;
+
+    /// Starts a CalDAV stand-in that only answers REPORTs carrying the
+    /// expected credentials and time range.
+    async fn mock_server() -> SocketAddr {
+        let make_svc = make_service_fn(|_| async {
+            Ok::<_, Infallible>(service_fn(|req: Request<Body>| async move {
+                let authorized = req
+                    .headers()
+                    .get(header::AUTHORIZATION)
+                    .is_some_and(|v| v == "Basic YWxpY2U6aHVudGVyMg==");
+                let depth = req.headers().get("Depth").cloned();
+                let method = req.method().clone();
+                let body = hyper::body::to_bytes(req.into_body()).await.unwrap();
+                let body = String::from_utf8_lossy(&body);
+                let resp = if !authorized {
+                    Response::builder()
+                        .status(StatusCode::UNAUTHORIZED)
+                        .body(Body::empty())
+                } else if method.as_str() != "REPORT"
+                    || depth.as_ref().map(|d| d.as_bytes()) != Some(b"1")
+                    || !body.contains(r#"start="20200302T000000Z" end="20200303T000000Z""#)
+                {
+                    Response::builder()
+                        .status(StatusCode::BAD_REQUEST)
+                        .body(Body::empty())
+                } else {
+                    Response::builder()
+                        .status(StatusCode::MULTI_STATUS)
+                        .body(Body::from(MULTISTATUS))
+                };
+                Ok::<_, Infallible>(resp.unwrap())
+            }))
+        });
+        let server = Server::bind(&([127, 0, 0, 1], 0).into()).serve(make_svc);
+        let addr = server.local_addr();
+        tokio::spawn(server);
+        addr
+    }
+
+    fn source(addr: SocketAddr, password: &str) -> CalDavSource {
+        CalDavSource {
+            url: format!("http://{}/cal/team/", addr),
+            username: Some("alice".to_string()),
+            password: Some(password.to_string()),
+        }
+    }
+
+    #[tokio::test]
+    async fn report_returns_calendar_data() {
+        let addr = mock_server().await;
+        let client: HttpsClient = hyper::Client::builder().build(HttpsConnector::new());
+        let from = Utc.with_ymd_and_hms(2020, 3, 2, 0, 0, 0).unwrap();
+        let until = Utc.with_ymd_and_hms(2020, 3, 3, 0, 0, 0).unwrap();
+
+        let locale = Locale::load(&LocaleConfig::default()).unwrap();
+        let objects = fetch(&client, &source(addr, "hunter2"), from, until)
+            .await
+            .unwrap();
+        assert_eq!(objects.len(), 2);
+
+        let events: Vec<_> = objects
+            .iter()
+            .flat_map(|o| crate::calendar::expand(&crate::calendar::parse(o), &locale, from, until))
+            .collect();
+        let summaries: Vec<_> = events.iter().map(|e| e.summary.as_str()).collect();
+        assert_eq!(summaries, ["Standup & coffee", "Review <draft>"]);
+        assert_eq!(
+            events[0].start,
+            Utc.with_ymd_and_hms(2020, 3, 2, 15, 0, 0).unwrap()
+        );
+    }
+
+    #[tokio::test]
+    async fn rejected_credentials_are_an_error() {
+        let addr = mock_server().await;
+        let client: HttpsClient = hyper::Client::builder().build(HttpsConnector::new());
+        let from = Utc.with_ymd_and_hms(2020, 3, 2, 0, 0, 0).unwrap();
+        let until = Utc.with_ymd_and_hms(2020, 3, 3, 0, 0, 0).unwrap();
+
+        let err = fetch(&client, &source(addr, "wrong"), from, until)
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("401"));
+    }
+}
@@ -0,0 +1,888 @@
+//! Agenda support built on a small iCalendar (RFC 5545) reader.
+//!
+//! Only the parts of the format a desk display needs are handled: `VEVENT`
+//! components, `DTSTART`/`DTEND`/`DURATION` with `TZID` parameters, and the
+//! common `RRULE` frequencies along with `EXDATE` and `RECURRENCE-ID`
+//! overrides.
+
+use crate::{
+    caldav, config::CalendarConfig, fonts::Fonts, locale::Locale, Color, Display, HttpsClient,
+};
+use chrono::{
+    DateTime, Datelike, Duration, FixedOffset, NaiveDate, NaiveDateTime, TimeZone, Utc, Weekday,
+};
+use chrono_tz::Tz;
+use embedded_graphics::{prelude::*, primitives::Rectangle, Drawing};
+use std::collections::HashMap;
+
+/// Events closer than this are drawn highlighted with an "in N min" label.
+const SOON_MINUTES: i64 = 15;
+
+/// A single, already expanded, occurrence of a calendar event.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Event {
+    pub summary: String,
+    pub start: DateTime<Utc>,
+    pub end: DateTime<Utc>,
+    pub all_day: bool,
+    /// Set once its alert has been dismissed with a button, after which it
+    /// is drawn like any other event.
+    pub acknowledged: bool,
+}
+
+/// Reads a calendar from a local path or an `http(s)://`/`webcal://` URL.
+pub async fn fetch(
+    client: &HttpsClient,
+    source: &str,
+) -> Result<String, Box<dyn std::error::Error + Send + Sync>> {
+    let url = if source.starts_with("webcal://") {
+        source.replacen("webcal://", "https://", 1)
+    } else {
+        source.to_string()
+    };
+    if url.starts_with("http://") || url.starts_with("https://") {
+        let resp = client.get(url.parse()?).await?;
+        if !resp.status().is_success() {
+            return Err(format!("{} returned {}", source, resp.status()).into());
+        }
+        let body = hyper::body::to_bytes(resp.into_body()).await?;
+        Ok(String::from_utf8_lossy(&body).into_owned())
+    } else {
+        Ok(tokio::fs::read_to_string(&url).await?)
+    }
+}
+
+/// Fetches every configured source, files, URLs and CalDAV collections, and
+/// returns the occurrences overlapping `[from, from + lookahead_days)`,
+/// sorted by start time.
+///
+/// A source that fails to load is reported and skipped so one broken feed
+/// doesn't blank the whole agenda.
+pub async fn load(
+    client: &HttpsClient,
+    config: &CalendarConfig,
+    locale: &Locale,
+    from: DateTime<Utc>,
+) -> Vec<Event> {
+    let until = from + Duration::days(config.lookahead_days);
+    let mut events = Vec::new();
+    for source in &config.sources {
+        match fetch(client, source).await {
+            Ok(text) => events.extend(expand(&parse(&text), locale, from, until)),
+            Err(e) => eprintln!("unable to load calendar {}: {}", source, e),
+        }
+    }
+    for source in &config.caldav {
+        match caldav::fetch(client, source, from, until).await {
+            Ok(objects) => {
+                for text in objects {
+                    events.extend(expand(&parse(&text), locale, from, until));
+                }
+            }
+            Err(e) => eprintln!("unable to load calendar {}: {}", source.url, e),
+        }
+    }
+    events.sort_by_key(|e| e.start);
+    events
+}
+
+#[derive(Clone, Copy, Debug)]
+enum Zone {
+    Utc,
+    Named(Tz),
+    Floating,
+}
+
+impl Zone {
+    /// Floating times, and dates, are taken to be in the configured zone.
+    fn resolve(self, naive: NaiveDateTime, locale: &Locale) -> Option<DateTime<Utc>> {
+        match self {
+            Zone::Utc => Some(Utc.from_utc_datetime(&naive)),
+            Zone::Named(tz) => tz
+                .from_local_datetime(&naive)
+                .earliest()
+                .map(|t| t.with_timezone(&Utc)),
+            Zone::Floating => locale.resolve(naive),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+struct Stamp {
+    naive: NaiveDateTime,
+    zone: Zone,
+    date_only: bool,
+}
+
+impl Stamp {
+    fn utc(self, locale: &Locale) -> Option<DateTime<Utc>> {
+        self.zone.resolve(self.naive, locale)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Freq {
+    Daily,
+    Weekly,
+    Monthly,
+    Yearly,
+}
+
+#[derive(Clone, Debug)]
+struct RRule {
+    freq: Freq,
+    interval: u32,
+    count: Option<u32>,
+    until: Option<Stamp>,
+    by_day: Vec<(Option<i32>, Weekday)>,
+    by_month_day: Vec<i32>,
+    by_month: Vec<u32>,
+}
+
+/// A `VEVENT` as written in the file, before recurrence expansion.
+#[derive(Clone, Debug, Default)]
+pub struct VEvent {
+    uid: Option<String>,
+    summary: String,
+    start: Option<Stamp>,
+    end: Option<Stamp>,
+    duration: Option<Duration>,
+    rrule: Option<RRule>,
+    exdates: Vec<Stamp>,
+    recurrence_id: Option<Stamp>,
+    cancelled: bool,
+}
+
+struct Property<'a> {
+    name: String,
+    params: Vec<(String, &'a str)>,
+    value: &'a str,
+}
+
+impl<'a> Property<'a> {
+    fn param(&self, name: &str) -> Option<&'a str> {
+        self.params
+            .iter()
+            .find(|(k, _)| k == name)
+            .map(|(_, v)| v.trim_matches('"'))
+    }
+}
+
+/// Joins folded content lines back together.
+fn unfold(text: &str) -> Vec<String> {
+    let mut lines: Vec<String> = Vec::new();
+    for line in text.lines() {
+        let line = line.trim_end_matches('\r');
+        if line.starts_with(' ') || line.starts_with('\t') {
+            if let Some(last) = lines.last_mut() {
+                last.push_str(&line[1..]);
+                continue;
+            }
+        }
+        lines.push(line.to_string());
+    }
+    lines
+}
+
+fn parse_property(line: &str) -> Option<Property<'_>> {
+    // the value starts at the first colon that isn't inside a quoted param
+    let mut quoted = false;
+    let split = line.char_indices().find(|&(_, c)| {
+        if c == '"' {
+            quoted = !quoted;
+        }
+        c == ':' && !quoted
+    })?;
+    let (head, value) = (&line[..split.0], &line[split.0 + 1..]);
+    let mut parts = head.split(';');
+    let name = parts.next()?.to_ascii_uppercase();
+    let params = parts
+        .filter_map(|p| {
+            let mut kv = p.splitn(2, '=');
+            Some((kv.next()?.to_ascii_uppercase(), kv.next()?))
+        })
+        .collect();
+    Some(Property {
+        name,
+        params,
+        value,
+    })
+}
+
+fn unescape(value: &str) -> String {
+    value
+        .replace("\\n", " ")
+        .replace("\\N", " ")
+        .replace("\\,", ",")
+        .replace("\\;", ";")
+        .replace("\\\\", "\\")
+}
+
+fn parse_stamp(value: &str, tzid: Option<&str>) -> Option<Stamp> {
+    let value = value.trim();
+    if value.len() == 8 {
+        let date = NaiveDate::parse_from_str(value, "%Y%m%d").ok()?;
+        return Some(Stamp {
+            naive: date.and_hms_opt(0, 0, 0)?,
+            zone: Zone::Floating,
+            date_only: true,
+        });
+    }
+    let (value, utc) = match value.strip_suffix('Z') {
+        Some(v) => (v, true),
+        None => (value, false),
+    };
+    let naive = NaiveDateTime::parse_from_str(value, "%Y%m%dT%H%M%S").ok()?;
+    let zone = if utc {
+        Zone::Utc
+    } else {
+        // some producers prefix the id with a slash to mark it as global
+        match tzid.and_then(|id| id.trim_start_matches('/').parse::<Tz>().ok()) {
+            Some(tz) => Zone::Named(tz),
+            None => Zone::Floating,
+        }
+    };
+    Some(Stamp {
+        naive,
+        zone,
+        date_only: false,
+    })
+}
+
+/// Parses an RFC 5545 duration such as `PT1H30M` or `-P1D`.
+fn parse_duration(value: &str) -> Option<Duration> {
+    let (negative, value) = match value.strip_prefix('-') {
+        Some(v) => (true, v),
+        None => (false, value.trim_start_matches('+')),
+    };
+    let mut total = Duration::zero();
+    let mut number = String::new();
+    for c in value.strip_prefix('P')?.chars() {
+        match c {
+            '0'..='9' => number.push(c),
+            'T' => {}
+            'W' | 'D' | 'H' | 'M' | 'S' => {
+                let n: i64 = number.parse().ok()?;
+                number.clear();
+                total += match c {
+                    'W' => Duration::weeks(n),
+                    'D' => Duration::days(n),
+                    'H' => Duration::hours(n),
+                    'M' => Duration::minutes(n),
+                    _ => Duration::seconds(n),
+                };
+            }
+            _ => return None,
+        }
+    }
+    Some(if negative { -total } else { total })
+}
+
+fn parse_weekday(value: &str) -> Option<Weekday> {
+    Some(match value {
+        "MO" => Weekday::Mon,
+        "TU" => Weekday::Tue,
+        "WE" => Weekday::Wed,
+        "TH" => Weekday::Thu,
+        "FR" => Weekday::Fri,
+        "SA" => Weekday::Sat,
+        "SU" => Weekday::Sun,
+        _ => return None,
+    })
+}
+
+fn parse_rrule(value: &str) -> Option<RRule> {
+    let mut rule = RRule {
+        freq: Freq::Daily,
+        interval: 1,
+        count: None,
+        until: None,
+        by_day: Vec::new(),
+        by_month_day: Vec::new(),
+        by_month: Vec::new(),
+    };
+    let mut freq = None;
+    for part in value.split(';') {
+        let mut kv = part.splitn(2, '=');
+        let (key, val) = (kv.next()?, kv.next()?);
+        match key {
+            "FREQ" => {
+                freq = Some(match val {
+                    "DAILY" => Freq::Daily,
+                    "WEEKLY" => Freq::Weekly,
+                    "MONTHLY" => Freq::Monthly,
+                    "YEARLY" => Freq::Yearly,
+                    // sub-daily rules make no sense on an agenda
+                    _ => return None,
+                })
+            }
+            "INTERVAL" => rule.interval = val.parse().ok().filter(|&i| i > 0)?,
+            "COUNT" => rule.count = val.parse().ok(),
+            "UNTIL" => rule.until = parse_stamp(val, None),
+            "BYDAY" => {
+                for day in val.split(',') {
+                    let split = day.len().checked_sub(2)?;
+                    let ordinal = day[..split].parse().ok();
+                    rule.by_day.push((ordinal, parse_weekday(&day[split..])?));
+                }
+            }
+            "BYMONTHDAY" => {
+                rule.by_month_day = val.split(',').filter_map(|d| d.parse().ok()).collect()
+            }
+            "BYMONTH" => {
+                rule.by_month = val
+                    .split(',')
+                    .map(|m| m.parse().ok().filter(|m| (1..=12).contains(m)))
+                    .collect::<Option<_>>()?
+            }
+            _ => {}
+        }
+    }
+    rule.freq = freq?;
+    // days of a whole year, e.g. every Monday, aren't expanded
+    let by_days = !rule.by_day.is_empty() || !rule.by_month_day.is_empty();
+    if rule.freq == Freq::Yearly && rule.by_month.is_empty() && by_days {
+        return None;
+    }
+    Some(rule)
+}
+
+/// Parses every `VEVENT` out of an iCalendar document.
+pub fn parse(text: &str) -> Vec<VEvent> {
+    let mut events = Vec::new();
+    let mut current: Option<VEvent> = None;
+    // nested components such as VALARM reuse property names, skip them
+    let mut depth = 0;
+    for line in unfold(text) {
+        let prop = match parse_property(&line) {
+            Some(prop) => prop,
+            None => continue,
+        };
+        match (prop.name.as_str(), prop.value) {
+            ("BEGIN", "VEVENT") => current = Some(VEvent::default()),
+            ("END", "VEVENT") => events.extend(current.take()),
+            ("BEGIN", _) if current.is_some() => depth += 1,
+            ("END", _) if current.is_some() => depth -= 1,
+            _ => {}
+        }
+        let event = match current.as_mut() {
+            Some(event) if depth == 0 => event,
+            _ => continue,
+        };
+        let tzid = prop.param("TZID");
+        match prop.name.as_str() {
+            "UID" => event.uid = Some(prop.value.to_string()),
+            "SUMMARY" => event.summary = unescape(prop.value),
+            "DTSTART" => event.start = parse_stamp(prop.value, tzid),
+            "DTEND" => event.end = parse_stamp(prop.value, tzid),
+            "DURATION" => event.duration = parse_duration(prop.value),
+            "RRULE" => event.rrule = parse_rrule(prop.value),
+            "EXDATE" => event
+                .exdates
+                .extend(prop.value.split(',').filter_map(|v| parse_stamp(v, tzid))),
+            "RECURRENCE-ID" => event.recurrence_id = parse_stamp(prop.value, tzid),
+            "STATUS" => event.cancelled = prop.value.eq_ignore_ascii_case("CANCELLED"),
+            _ => {}
+        }
+    }
+    events
+}
+
+fn add_months(date: NaiveDate, months: i32, day: u32) -> Option<NaiveDate> {
+    let total = date.year() * 12 + date.month0() as i32 + months;
+    NaiveDate::from_ymd_opt(total.div_euclid(12), total.rem_euclid(12) as u32 + 1, day)
+}
+
+fn days_in_month(year: i32, month: u32) -> u32 {
+    let (y, m) = if month == 12 {
+        (year + 1, 1)
+    } else {
+        (year, month + 1)
+    };
+    NaiveDate::from_ymd_opt(y, m, 1)
+        .and_then(|d| d.pred_opt())
+        .map_or(31, |d| d.day())
+}
+
+/// Days of the month starting on `first` picked by `BYDAY` and
+/// `BYMONTHDAY`, only those matching both when both are given, or `day`
+/// without either.
+fn month_days(rule: &RRule, first: NaiveDate, day: u32) -> Vec<NaiveDate> {
+    let len = days_in_month(first.year(), first.month());
+    let mut by_day: Vec<u32> = Vec::new();
+    for &(ordinal, weekday) in &rule.by_day {
+        let matching: Vec<u32> = (1..=len)
+            .filter(|&d| first.with_day(d).map(|d| d.weekday()) == Some(weekday))
+            .collect();
+        match ordinal {
+            Some(n) if n > 0 => by_day.extend(matching.get(n as usize - 1)),
+            Some(n) if n < 0 => by_day.extend(
+                matching
+                    .len()
+                    .checked_sub(n.unsigned_abs() as usize)
+                    .and_then(|i| matching.get(i)),
+            ),
+            _ => by_day.extend(matching),
+        }
+    }
+    let by_month_day: Vec<u32> = rule
+        .by_month_day
+        .iter()
+        .filter_map(|&d| match d {
+            d if d > 0 && d as u32 <= len => Some(d as u32),
+            d if d < 0 && d.unsigned_abs() <= len => Some(len + 1 - d.unsigned_abs()),
+            _ => None,
+        })
+        .collect();
+    let days = match (rule.by_day.is_empty(), rule.by_month_day.is_empty()) {
+        (true, true) => vec![day],
+        (false, true) => by_day,
+        (true, false) => by_month_day,
+        (false, false) => by_day
+            .into_iter()
+            .filter(|d| by_month_day.contains(d))
+            .collect(),
+    };
+    days.into_iter().filter_map(|d| first.with_day(d)).collect()
+}
+
+/// The first day of a recurrence period: the day itself, or the first day
+/// of its week, month or year.
+fn period_start(rule: &RRule, start: NaiveDate, period: u32) -> Option<NaiveDate> {
+    let step = period.checked_mul(rule.interval)? as i32;
+    match rule.freq {
+        Freq::Daily => Some(start + Duration::days(step as i64)),
+        Freq::Weekly => Some(
+            start - Duration::days(start.weekday().num_days_from_monday() as i64)
+                + Duration::weeks(step as i64),
+        ),
+        Freq::Monthly => add_months(start, step, 1),
+        Freq::Yearly => NaiveDate::from_ymd_opt(start.year() + step, 1, 1),
+    }
+}
+
+/// The first period that can hold a date on or after `date`, so expansion
+/// need not walk every period since `start`.
+fn first_period(rule: &RRule, start: NaiveDate, date: NaiveDate) -> u32 {
+    let elapsed = match rule.freq {
+        Freq::Daily => (date - start).num_days(),
+        Freq::Weekly => ((date - start).num_days() + start.weekday().num_days_from_monday() as i64)
+            .div_euclid(7),
+        Freq::Monthly => {
+            (date.year() - start.year()) as i64 * 12 + date.month0() as i64 - start.month0() as i64
+        }
+        Freq::Yearly => (date.year() - start.year()) as i64,
+    };
+    (elapsed.max(0) / rule.interval as i64) as u32
+}
+
+/// Dates inside one recurrence period, in ascending order.
+fn period_dates(rule: &RRule, start: NaiveDate, period: u32) -> Vec<NaiveDate> {
+    let step = (period * rule.interval) as i32;
+    let mut dates = match rule.freq {
+        Freq::Daily => vec![start + Duration::days(step as i64)],
+        Freq::Weekly => {
+            let week = start - Duration::days(start.weekday().num_days_from_monday() as i64)
+                + Duration::weeks(step as i64);
+            if rule.by_day.is_empty() {
+                vec![week + Duration::days(start.weekday().num_days_from_monday() as i64)]
+            } else {
+                rule.by_day
+                    .iter()
+                    .map(|(_, day)| week + Duration::days(day.num_days_from_monday() as i64))
+                    .collect()
+            }
+        }
+        Freq::Monthly => match add_months(start, step, 1) {
+            Some(first) => month_days(rule, first, start.day()),
+            None => return Vec::new(),
+        },
+        Freq::Yearly if rule.by_month.is_empty() => {
+            NaiveDate::from_ymd_opt(start.year() + step, start.month(), start.day())
+                .into_iter()
+                .collect()
+        }
+        Freq::Yearly => rule
+            .by_month
+            .iter()
+            .filter_map(|&month| NaiveDate::from_ymd_opt(start.year() + step, month, 1))
+            .flat_map(|first| month_days(rule, first, start.day()))
+            .collect(),
+    };
+    // in the other frequencies BYMONTH only narrows the dates down
+    if rule.freq != Freq::Yearly && !rule.by_month.is_empty() {
+        dates.retain(|d| rule.by_month.contains(&d.month()));
+    }
+    dates.sort();
+    dates.dedup();
+    dates
+}
+
+/// Expands recurring events and returns the occurrences overlapping
+/// `[from, until)`.
+pub fn expand(
+    vevents: &[VEvent],
+    locale: &Locale,
+    from: DateTime<Utc>,
+    until: DateTime<Utc>,
+) -> Vec<Event> {
+    // moved or cancelled instances are listed as their own VEVENT with a
+    // RECURRENCE-ID pointing back at the original occurrence
+    let mut overridden: HashMap<&str, Vec<DateTime<Utc>>> = HashMap::new();
+    for v in vevents {
+        let id = v.recurrence_id.and_then(|id| id.utc(locale));
+        if let (Some(uid), Some(id)) = (&v.uid, id) {
+            overridden.entry(uid.as_str()).or_default().push(id);
+        }
+    }
+
+    let mut out = Vec::new();
+    for v in vevents {
+        let start = match v.start {
+            Some(start) => start,
+            None => continue,
+        };
+        let length = match (v.end, v.duration) {
+            (Some(end), _) => end.naive - start.naive,
+            (None, Some(duration)) => duration,
+            (None, None) if start.date_only => Duration::days(1),
+            (None, None) => Duration::zero(),
+        };
+        let mut skip: Vec<_> = v.exdates.iter().filter_map(|d| d.utc(locale)).collect();
+        if v.recurrence_id.is_none() {
+            if let Some(ids) = v.uid.as_deref().and_then(|uid| overridden.get(uid)) {
+                skip.extend(ids);
+            }
+        }
+        let mut push = |naive: NaiveDateTime| {
+            let begin = match start.zone.resolve(naive, locale) {
+                Some(begin) => begin,
+                None => return,
+            };
+            let end = start
+                .zone
+                .resolve(naive + length, locale)
+                .unwrap_or(begin + length);
+            if !v.cancelled && !skip.contains(&begin) && end > from && begin < until {
+                out.push(Event {
+                    summary: v.summary.clone(),
+                    start: begin,
+                    end,
+                    all_day: start.date_only,
+                    acknowledged: false,
+                });
+            }
+        };
+
+        let rule = match &v.rrule {
+            Some(rule) => rule,
+            None => {
+                push(start.naive);
+                continue;
+            }
+        };
+        // a COUNT has to be walked from the start, otherwise skip straight
+        // to the periods that can still overlap the window, allowing a day
+        // either side for the difference between the zone and UTC
+        let first = match rule.count {
+            Some(_) => 0,
+            None => first_period(
+                rule,
+                start.naive.date(),
+                (from - length).date_naive() - Duration::days(1),
+            ),
+        };
+        let last = until.date_naive() + Duration::days(1);
+        let mut emitted = 0;
+        'periods: for period in first.. {
+            match period_start(rule, start.naive.date(), period) {
+                Some(day) if day <= last => {}
+                _ => break,
+            }
+            for date in period_dates(rule, start.naive.date(), period) {
+                let naive = date.and_time(start.naive.time());
+                if naive < start.naive {
+                    continue;
+                }
+                let begin = match start.zone.resolve(naive, locale) {
+                    Some(begin) => begin,
+                    None => continue,
+                };
+                let after = rule
+                    .until
+                    .and_then(|u| u.utc(locale))
+                    .is_some_and(|u| begin > u);
+                if begin >= until || after {
+                    break 'periods;
+                }
+                if rule.count.is_some_and(|c| emitted >= c) {
+                    break 'periods;
+                }
+                emitted += 1;
+                push(naive);
+            }
+        }
+    }
+    out
+}
+
+/// Whether `event` is starting within [`SOON_MINUTES`] or under way, and
+/// so highlighted until it is acknowledged. All-day events never are.
+pub fn alerting(event: &Event, now: DateTime<Utc>) -> bool {
+    !event.acknowledged
+        && !event.all_day
+        && event.end > now
+        && (event.start - now).num_minutes() < SOON_MINUTES
+}
+
+/// When the agenda will next look different: an event coming within
+/// [`SOON_MINUTES`], its "in N min" ticking over, or it starting or ending.
+pub fn next_change(events: &[Event], now: DateTime<Utc>) -> Option<DateTime<Utc>> {
+    events
+        .iter()
+        .filter(|e| e.end > now)
+        .flat_map(|e| {
+            let mut changes = vec![e.start, e.end];
+            if e.all_day {
+                return changes;
+            }
+            changes.push(e.start - Duration::minutes(SOON_MINUTES));
+            let minutes = (e.start - now).num_minutes();
+            if e.start > now && minutes < SOON_MINUTES {
+                changes.push(e.start - Duration::minutes(minutes));
+                changes.push(e.start - Duration::minutes(minutes - 1));
+            }
+            changes
+        })
+        .filter(|&t| t > now)
+        .min()
+}
+
+/// Draws up to `lines` upcoming events, one per line, starting at `origin`.
+///
+/// Events starting within the next [`SOON_MINUTES`] are drawn inverted with an
+/// "in N min" label instead of the start time.
+pub fn draw_agenda(
+    display: &mut Display,
+    fonts: &Fonts,
+    locale: &Locale,
+    events: &[Event],
+    now: DateTime<FixedOffset>,
+    origin: Coord,
+    lines: usize,
+) {
+    let now_utc = now.with_timezone(&Utc);
+    let upcoming = events.iter().filter(|e| e.end > now_utc).take(lines);
+    for (i, event) in upcoming.enumerate() {
+        let minutes = (event.start - now_utc).num_minutes();
+        let alert = alerting(event, now_utc);
+        let soon = alert && event.start > now_utc;
+        let start = locale.localize(event.start);
+        let label = if event.all_day {
+            if start.date_naive() <= now.date_naive() {
+                "today".to_string()
+            } else {
+                locale.format(&start, "%a")
+            }
+        } else if soon {
+            format!("in {} min", minutes + 1)
+        } else if event.start <= now_utc {
+            "now".to_string()
+        } else if start.date_naive() == now.date_naive() {
+            locale.time(&start)
+        } else {
+            format!("{} {}", locale.format(&start, "%a"), locale.time(&start))
+        };
+        let right = display.clip().bottom_right[0] as i32 + 1;
+        let line = fonts.body.fit(
+            &format!("{} {}", label, event.summary),
+            (right - origin[0]).max(0) as u32,
+        );
+        let line_height = fonts.body.height() as i32;
+        let y = origin[1] + i as i32 * line_height;
+        let (fg, bg) = if soon {
+            (Color::White, Color::Red)
+        } else if alert {
+            (Color::Red, Color::White)
+        } else {
+            (Color::Black, Color::White)
+        };
+        if soon {
+            display.draw(
+                Rectangle::new(
+                    Coord::new(origin[0], y),
+                    Coord::new(right - 1, y + line_height - 1),
+                )
+                .fill(Some(bg)),
+            );
+        }
+        fonts
+            .body
+            .draw(display, &line, Coord::new(origin[0], y), fg, bg);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::LocaleConfig;
+
+    fn at(y: i32, m: u32, d: u32, h: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(y, m, d, h, 0, 0).unwrap()
+    }
+
+    /// Start times of the occurrences of `events`, given as VEVENT bodies,
+    /// within the years 2020 to 2022.
+    fn starts(events: &[&str]) -> Vec<DateTime<Utc>> {
+        let text: String = events
+            .iter()
+            .map(|body| format!("BEGIN:VEVENT\r\n{}\r\nEND:VEVENT\r\n", body.trim()))
+            .collect();
+        let text = format!("BEGIN:VCALENDAR\r\n{}END:VCALENDAR\r\n", text);
+        let locale = Locale::load(&LocaleConfig {
+            timezone: Some("America/Chicago".to_string()),
+            ..LocaleConfig::default()
+        })
+        .unwrap();
+        let mut events = expand(&parse(&text), &locale, at(2020, 1, 1, 0), at(2023, 1, 1, 0));
+        events.sort_by_key(|e| e.start);
+        events.into_iter().map(|e| e.start).collect()
+    }
+
+    #[test]
+    fn weekly_on_several_days() {
+        let starts = starts(&["UID:a
+DTSTART:20200302T090000Z
+RRULE:FREQ=WEEKLY;BYDAY=MO,WE;COUNT=4"]);
+        assert_eq!(
+            starts,
+            [
+                at(2020, 3, 2, 9),
+                at(2020, 3, 4, 9),
+                at(2020, 3, 9, 9),
+                at(2020, 3, 11, 9)
+            ]
+        );
+    }
+
+    #[test]
+    fn monthly_last_weekday() {
+        let starts = starts(&["UID:a
+DTSTART:20200131T090000Z
+RRULE:FREQ=MONTHLY;BYDAY=-1FR;COUNT=3"]);
+        assert_eq!(
+            starts,
+            [at(2020, 1, 31, 9), at(2020, 2, 28, 9), at(2020, 3, 27, 9)]
+        );
+    }
+
+    #[test]
+    fn monthly_day_and_weekday_must_both_match() {
+        let starts = starts(&["UID:a
+DTSTART:20200101T090000Z
+RRULE:FREQ=MONTHLY;BYDAY=FR;BYMONTHDAY=13;UNTIL=20211231T000000Z"]);
+        assert_eq!(
+            starts,
+            [at(2020, 3, 13, 9), at(2020, 11, 13, 9), at(2021, 8, 13, 9)]
+        );
+    }
+
+    #[test]
+    fn yearly_in_a_month() {
+        let starts = starts(&["UID:a
+DTSTART:20201126T180000Z
+RRULE:FREQ=YEARLY;BYMONTH=11;BYDAY=4TH"]);
+        assert_eq!(
+            starts,
+            [
+                at(2020, 11, 26, 18),
+                at(2021, 11, 25, 18),
+                at(2022, 11, 24, 18)
+            ]
+        );
+    }
+
+    #[test]
+    fn bymonth_narrows_daily_rules() {
+        let starts = starts(&["UID:a
+DTSTART:20200130T090000Z
+RRULE:FREQ=DAILY;BYMONTH=2;COUNT=2"]);
+        assert_eq!(starts, [at(2020, 2, 1, 9), at(2020, 2, 2, 9)]);
+    }
+
+    #[test]
+    fn old_rules_still_reach_the_window() {
+        let daily = starts(&["UID:a
+DTSTART:20050103T090000Z
+RRULE:FREQ=DAILY;INTERVAL=2"]);
+        assert_eq!(daily.len(), 548);
+        assert_eq!(daily[0], at(2020, 1, 1, 9));
+
+        let monthly = starts(&["UID:a
+DTSTART:19990315T090000Z
+RRULE:FREQ=MONTHLY;INTERVAL=5;BYMONTHDAY=15"]);
+        assert_eq!(monthly[..2], [at(2020, 1, 15, 9), at(2020, 6, 15, 9)]);
+    }
+
+    #[test]
+    fn unsupported_rules_are_not_expanded() {
+        let starts = starts(&["UID:a
+DTSTART:20200106T090000Z
+RRULE:FREQ=YEARLY;BYDAY=MO"]);
+        assert_eq!(starts, [at(2020, 1, 6, 9)]);
+    }
+
+    #[test]
+    fn exdates_are_skipped() {
+        let starts = starts(&["UID:a
+DTSTART;TZID=Europe/Berlin:20200302T100000
+RRULE:FREQ=DAILY;COUNT=3
+EXDATE;TZID=Europe/Berlin:20200303T100000"]);
+        assert_eq!(starts, [at(2020, 3, 2, 9), at(2020, 3, 4, 9)]);
+    }
+
+    #[test]
+    fn overrides_replace_their_occurrence() {
+        let starts = starts(&[
+            "UID:a
+DTSTART:20200302T090000Z
+RRULE:FREQ=DAILY;COUNT=3",
+            "UID:a
+RECURRENCE-ID:20200303T090000Z
+DTSTART:20200303T140000Z",
+            "UID:a
+RECURRENCE-ID:20200304T090000Z
+DTSTART:20200304T090000Z
+STATUS:CANCELLED",
+        ]);
+        assert_eq!(starts, [at(2020, 3, 2, 9), at(2020, 3, 3, 14)]);
+    }
+
+    #[test]
+    fn floating_times_are_in_the_configured_zone() {
+        let starts = starts(&[
+            "UID:a
+DTSTART:20200302T090000",
+            "UID:b
+DTSTART;VALUE=DATE:20200303",
+        ]);
+        assert_eq!(starts, [at(2020, 3, 2, 15), at(2020, 3, 3, 6)]);
+    }
+
+    #[test]
+    fn all_day_events_never_alert() {
+        let event = Event {
+            summary: "holiday".to_string(),
+            start: at(2020, 3, 2, 0),
+            end: at(2020, 3, 3, 0),
+            all_day: true,
+            acknowledged: false,
+        };
+        assert!(!alerting(&event, at(2020, 3, 2, 12)));
+        let timed = Event {
+            all_day: false,
+            ..event
+        };
+        assert!(alerting(&timed, at(2020, 3, 2, 12)));
+    }
+}
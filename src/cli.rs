@@ -0,0 +1,239 @@
+//! Command line parsing and the one-shot commands that don't need the
+//! dashboard's data sources.
+
+use crate::{
+    display::{Panel, Rotation},
+    dither::{self, Dither, Fit},
+    fonts::Fonts,
+    Color, Display,
+};
+use clap::{App, AppSettings, Arg, ArgMatches, SubCommand};
+use embedded_graphics::{drawable::Pixel, prelude::*, Drawing};
+use image::{DynamicImage, ImageOutputFormat};
+use serde::{de::IntoDeserializer, Deserialize};
+use serialport::SerialPortType;
+use std::{
+    fs,
+    io::{self, Write},
+    path::Path,
+};
+
+type Error = Box<dyn std::error::Error + Send + Sync>;
+
+pub fn app() -> App<'static, 'static> {
+    let config = Arg::with_name("config")
+        .short("c")
+        .long("config")
+        .value_name("FILE")
+        .global(true)
+        .help("Config file to read [default: config.toml]");
+    let port = Arg::with_name("port")
+        .short("p")
+        .long("port")
+        .value_name("PORT")
+        .global(true)
+        .help("Serial port, tcp://host:port or http:// URL of the panel [default: [device] in the config]");
+
+    let display = Arg::with_name("display")
+        .short("d")
+        .long("display")
+        .value_name("NAME")
+        .global(true)
+        .help("Only use this one of the configured [[displays]], render takes the first otherwise");
+
+    App::new(env!("CARGO_PKG_NAME"))
+        .version(env!("CARGO_PKG_VERSION"))
+        .about("Draws a dashboard and sends it to an e-paper panel")
+        .setting(AppSettings::VersionlessSubcommands)
+        .args(&[config, port, display])
+        .subcommand(
+            SubCommand::with_name("render")
+                .about("Draws the dashboard once to a file instead of the panel")
+                .arg(
+                    Arg::with_name("output")
+                        .short("o")
+                        .long("output")
+                        .value_name("FILE")
+                        .default_value("-")
+                        .help("Where to write the frame, - for stdout"),
+                )
+                .arg(
+                    Arg::with_name("format")
+                        .short("f")
+                        .long("format")
+                        .possible_values(&["png", "bin"])
+                        .help("png for a preview, bin for the raw panel buffer [default: from the file extension, else png]"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("push")
+                .about("Sends an image or a raw panel buffer to the panel")
+                .arg(
+                    Arg::with_name("FILE")
+                        .required(true)
+                        .help("PNG, JPEG or BMP image, or a .bin buffer from render"),
+                )
+                .arg(
+                    Arg::with_name("fit")
+                        .long("fit")
+                        .possible_values(&["crop", "letterbox", "stretch"])
+                        .default_value("letterbox"),
+                )
+                .arg(
+                    Arg::with_name("dither")
+                        .long("dither")
+                        .possible_values(&["floyd-steinberg", "ordered", "threshold"])
+                        .default_value("floyd-steinberg"),
+                ),
+        )
+        .subcommand(SubCommand::with_name("clear").about("Blanks the panel"))
+        .subcommand(
+            SubCommand::with_name("test-pattern")
+                .about("Draws a checkerboard, border and the panel geometry"),
+        )
+        .subcommand(SubCommand::with_name("list-ports").about("Lists the serial ports found"))
+        .subcommand(
+            SubCommand::with_name("serve")
+                .about("Serves frames over HTTP for displays that poll")
+                .arg(
+                    Arg::with_name("listen")
+                        .short("l")
+                        .long("listen")
+                        .value_name("ADDR")
+                        .help("Address to listen on [default: [server] in the config, else 0.0.0.0:8080]"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("run")
+                .about("Draws the dashboard, and keeps it up to date if [daemon] is set (default)"),
+        )
+}
+
+pub fn list_ports() -> Result<(), Error> {
+    for port in serialport::available_ports()? {
+        match port.port_type {
+            SerialPortType::UsbPort(usb) => println!(
+                "{}\tUSB {:04x}:{:04x}\t{}\t{}",
+                port.port_name,
+                usb.vid,
+                usb.pid,
+                usb.serial_number.as_deref().unwrap_or("-"),
+                usb.product.as_deref().unwrap_or("-"),
+            ),
+            SerialPortType::PciPort => println!("{}\tPCI", port.port_name),
+            SerialPortType::BluetoothPort => println!("{}\tBluetooth", port.port_name),
+            SerialPortType::Unknown => println!("{}", port.port_name),
+        }
+    }
+    Ok(())
+}
+
+/// Parses one of the config's enum names given on the command line.
+fn parse<'de, T: Deserialize<'de>>(value: &'de str) -> Result<T, Error> {
+    let deserializer: serde::de::value::StrDeserializer<serde::de::value::Error> =
+        value.into_deserializer();
+    Ok(T::deserialize(deserializer)?)
+}
+
+/// Reads the file given to `push`. Files ending in `.bin` are sent as they
+/// are, anything else is decoded as an image and dithered to the panel.
+pub fn load_frame(panel: &Panel, args: &ArgMatches) -> Result<Vec<u8>, Error> {
+    let path = Path::new(args.value_of("FILE").unwrap_or_default());
+    if path.extension().is_some_and(|ext| ext == "bin") {
+        let frame = fs::read(path)?;
+        if frame.len() != panel.buffer_len() {
+            return Err(format!(
+                "{} is {} bytes but the panel takes {}",
+                path.display(),
+                frame.len(),
+                panel.buffer_len()
+            )
+            .into());
+        }
+        return Ok(frame);
+    }
+
+    let fit: Fit = parse(args.value_of("fit").unwrap_or("letterbox"))?;
+    let method: Dither = parse(args.value_of("dither").unwrap_or("floyd-steinberg"))?;
+    let (width, height) = panel.size();
+    let bitmap = dither::load(path, width, height, fit, method)?;
+    let mut buf = panel.blank();
+    bitmap.draw(&mut Display::new(&mut buf, *panel), Coord::new(0, 0));
+    Ok(buf)
+}
+
+/// Writes a drawn frame for `render`, as a PNG preview or the raw buffer.
+pub fn write_frame(
+    panel: &Panel,
+    buf: &[u8],
+    output: Option<&str>,
+    format: Option<&str>,
+) -> Result<(), Error> {
+    let output = output.unwrap_or("-");
+    let format = format.unwrap_or(if output.ends_with(".bin") {
+        "bin"
+    } else {
+        "png"
+    });
+    let bytes = if format == "bin" {
+        buf.to_vec()
+    } else {
+        let mut png = Vec::new();
+        DynamicImage::ImageRgb8(panel.preview(buf)).write_to(&mut png, ImageOutputFormat::Png)?;
+        png
+    };
+    if output == "-" {
+        io::stdout().write_all(&bytes)?;
+    } else {
+        fs::write(output, bytes)?;
+    }
+    Ok(())
+}
+
+/// An 8 pixel checkerboard framed by a one pixel border, with the panel's
+/// geometry written in the middle, to check wiring and orientation.
+pub fn draw_test_pattern(display: &mut Display, fonts: &Fonts) {
+    let panel = display.panel;
+    let (width, height) = panel.size();
+    let pixels = (0..height)
+        .flat_map(|y| (0..width).map(move |x| (x, y)))
+        .map(|(x, y)| {
+            let border = x == 0 || y == 0 || x == width - 1 || y == height - 1;
+            let black = border || (x / 8 + y / 8) % 2 == 0;
+            let color = if black { Color::Black } else { Color::White };
+            Pixel(UnsignedCoord::new(x, y), color)
+        });
+    display.draw(pixels);
+
+    let lines = [
+        format!("{}x{}", panel.width, panel.height),
+        format!(
+            "rotated {}",
+            match panel.rotation {
+                Rotation::Deg0 => 0,
+                Rotation::Deg90 => 90,
+                Rotation::Deg180 => 180,
+                Rotation::Deg270 => 270,
+            }
+        ),
+        if panel.tricolor {
+            "tri-color"
+        } else {
+            "black and white"
+        }
+        .to_string(),
+    ];
+    let face = &fonts.body;
+    let line_height = face.height() as i32;
+    let top = (height as i32 - line_height * lines.len() as i32) / 2;
+    for (i, line) in lines.iter().enumerate() {
+        let x = (width as i32 - face.width(line) as i32) / 2;
+        let origin = Coord::new(x, top + line_height * i as i32);
+        face.draw(display, line, origin, Color::Black, Color::White);
+    }
+    // the red plane gets a bar along the top so it can be checked too
+    if panel.tricolor {
+        let bar = (2..width - 2).flat_map(|x| (2..6).map(move |y| (x, y)));
+        display.draw(bar.map(|(x, y)| Pixel(UnsignedCoord::new(x, y), Color::Red)));
+    }
+}
@@ -0,0 +1,198 @@
+//! The clock, either as text or as big digits drawn from segments so it
+//! scales to any height.
+
+use crate::{
+    config::{ClockConfig, ClockStyle},
+    fonts::Fonts,
+    locale::Locale,
+    Color, Display,
+};
+use chrono::{DateTime, FixedOffset, Timelike};
+use embedded_graphics::{drawable::Pixel, prelude::*, Drawing};
+
+/// Segments lit for each digit, bit 0 to 6 being `a` to `g`: top, top
+/// right, bottom right, bottom, bottom left, top left and middle.
+const DIGITS: [u8; 10] = [
+    0b011_1111, 0b000_0110, 0b101_1011, 0b100_1111, 0b110_0110, 0b110_1101, 0b111_1101, 0b000_0111,
+    0b111_1111, 0b110_1111,
+];
+
+/// Geometry of one digit of the given height.
+#[derive(Clone, Copy)]
+struct Digit {
+    height: i32,
+    width: i32,
+    /// Half the stroke thickness.
+    radius: i32,
+    smooth: bool,
+}
+
+impl Digit {
+    fn new(height: u32, smooth: bool) -> Self {
+        let height = height.max(10) as i32;
+        Digit {
+            height,
+            width: height / 2 + 1,
+            radius: (height / 20).max(1),
+            smooth,
+        }
+    }
+
+    fn gap(&self) -> i32 {
+        (self.radius / 2).max(1)
+    }
+
+    /// Distance between characters.
+    fn spacing(&self) -> i32 {
+        (self.height / 8).max(2)
+    }
+
+    /// Whether `(x, y)` lies on the bar from `from` to `to`, which must be
+    /// horizontal or vertical. Seven-segment bars taper to a point at each
+    /// end, smooth ones are rounded.
+    fn on_bar(&self, from: (i32, i32), to: (i32, i32), x: i32, y: i32) -> bool {
+        let r = self.radius;
+        // distance along the bar past either end, and across it
+        let (along, across) = if from.1 == to.1 {
+            ((from.0 - x).max(x - to.0).max(0), (y - from.1).abs())
+        } else {
+            ((from.1 - y).max(y - to.1).max(0), (x - from.0).abs())
+        };
+        if self.smooth {
+            along * along + across * across <= r * r
+        } else {
+            along + across <= r
+        }
+    }
+
+    fn draw(&self, display: &mut Display, digit: usize, origin: Coord) {
+        let (r, g) = (self.radius, self.gap());
+        let (left, right) = (r, self.width - 1 - r);
+        let (top, middle, bottom) = (r, self.height / 2, self.height - 1 - r);
+        let bars = [
+            ((left + g, top), (right - g, top)),
+            ((right, top + g), (right, middle - g)),
+            ((right, middle + g), (right, bottom - g)),
+            ((left + g, bottom), (right - g, bottom)),
+            ((left, middle + g), (left, bottom - g)),
+            ((left, top + g), (left, middle - g)),
+            ((left + g, middle), (right - g, middle)),
+        ];
+        let lit = DIGITS[digit % 10];
+        let pixels = (0..self.height)
+            .flat_map(|y| (0..self.width).map(move |x| (x, y)))
+            .filter(|&(x, y)| {
+                bars.iter()
+                    .enumerate()
+                    .any(|(i, &(from, to))| lit & (1 << i) != 0 && self.on_bar(from, to, x, y))
+            })
+            .map(|(x, y)| origin + Coord::new(x, y))
+            .filter(|p| p[0] >= 0 && p[1] >= 0)
+            .map(|p| Pixel(p.to_unsigned(), Color::Black));
+        display.draw(pixels);
+    }
+
+    /// Draws a colon, returning its width.
+    fn colon(&self, display: &mut Display, origin: Coord) -> i32 {
+        let r = self.radius;
+        let x = self.spacing() / 2 + r;
+        for y in [self.height / 3, self.height * 2 / 3] {
+            let pixels = (-r..=r)
+                .flat_map(|dy| (-r..=r).map(move |dx| (dx, dy)))
+                .filter(|&(dx, dy)| !self.smooth || dx * dx + dy * dy <= r * r)
+                .map(|(dx, dy)| origin + Coord::new(x + dx, y + dy))
+                .filter(|p| p[0] >= 0 && p[1] >= 0)
+                .map(|p| Pixel(p.to_unsigned(), Color::Black));
+            display.draw(pixels);
+        }
+        2 * x + 1
+    }
+
+    /// Draws two digits, leaving the first blank if `blank_zero` is set
+    /// and it is zero, returning the width used.
+    fn pair(&self, display: &mut Display, value: u32, blank_zero: bool, origin: Coord) -> i32 {
+        if value >= 10 || !blank_zero {
+            self.draw(display, (value / 10) as usize, origin);
+        }
+        let second = self.width + self.spacing();
+        self.draw(
+            display,
+            (value % 10) as usize,
+            origin + Coord::new(second, 0),
+        );
+        second + self.width
+    }
+}
+
+/// Hour to show and, in 12-hour mode, the locale's AM/PM marker if it has
+/// one.
+fn hour(
+    config: &ClockConfig,
+    locale: &Locale,
+    now: DateTime<FixedOffset>,
+) -> (u32, Option<String>) {
+    if config.hour12 {
+        let meridiem = Some(locale.format(&now, "%p")).filter(|m| !m.is_empty());
+        (now.hour12().1, meridiem)
+    } else {
+        (now.hour(), None)
+    }
+}
+
+/// Draws the time with its top left corner at the configured position.
+pub fn draw_clock(
+    display: &mut Display,
+    fonts: &Fonts,
+    locale: &Locale,
+    config: &ClockConfig,
+    now: DateTime<FixedOffset>,
+) {
+    let origin = Coord::new(config.position.0, config.position.1);
+    let (hour, meridiem) = hour(config, locale, now);
+    let smooth = match config.style {
+        ClockStyle::Text => {
+            let mut formatted = format!("{:02}:{:02}", hour, now.minute());
+            if config.seconds {
+                formatted.push_str(&format!(":{:02}", now.second()));
+            }
+            fonts
+                .huge
+                .draw(display, &formatted, origin, Color::Black, Color::White);
+            if let Some(meridiem) = meridiem {
+                let x = fonts.huge.width(&formatted) as i32 + 2;
+                fonts.caption.draw(
+                    display,
+                    &meridiem,
+                    origin + Coord::new(x, 0),
+                    Color::Black,
+                    Color::White,
+                );
+            }
+            return;
+        }
+        ClockStyle::SevenSegment => false,
+        ClockStyle::Smooth => true,
+        ClockStyle::Hidden => return,
+    };
+
+    let digit = Digit::new(config.digit_height, smooth);
+    let mut x = digit.pair(display, hour, config.hour12, origin) + digit.spacing();
+    x += digit.colon(display, origin + Coord::new(x, 0)) + digit.spacing();
+    x += digit.pair(display, now.minute(), false, origin + Coord::new(x, 0)) + digit.spacing();
+
+    // AM/PM sits top right, seconds in small digits on the baseline
+    if let Some(meridiem) = meridiem {
+        fonts.caption.draw(
+            display,
+            &meridiem,
+            origin + Coord::new(x, 0),
+            Color::Black,
+            Color::White,
+        );
+    }
+    if config.seconds {
+        let small = Digit::new(config.digit_height / 2, smooth);
+        let y = digit.height - small.height;
+        small.pair(display, now.second(), false, origin + Coord::new(x, y));
+    }
+}
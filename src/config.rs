@@ -0,0 +1,605 @@
+//! Runtime configuration, read from a TOML file.
+//!
+//! Every section is optional so an empty (or missing) file reproduces the
+//! original weather + news layout.
+
+use crate::{
+    display::{BitOrder, Panel, Rotation},
+    dither::{Dither, Fit},
+};
+use chrono::{NaiveTime, Weekday};
+use chrono_tz::Tz;
+use serde_derive::Deserialize;
+use std::{
+    collections::HashMap,
+    convert::TryFrom,
+    fs,
+    io::ErrorKind,
+    path::{Path, PathBuf},
+};
+
+pub const DEFAULT_PATH: &str = "config.toml";
+
+#[derive(Deserialize, Debug, Default)]
+#[serde(default)]
+pub struct Config {
+    pub panel: PanelConfig,
+    pub device: DeviceConfig,
+    /// What to show in the headline area at the bottom of the screen.
+    /// Defaults to the calendar when one is configured, news otherwise.
+    pub bottom: Option<Widget>,
+    /// Directory of replacement weather icons, see `icons.rs`.
+    pub icons: Option<PathBuf>,
+    pub fonts: FontsConfig,
+    pub clock: ClockConfig,
+    pub locale: LocaleConfig,
+    pub worldclock: Option<WorldClockConfig>,
+    pub countdown: Option<CountdownConfig>,
+    pub calendar: Option<CalendarConfig>,
+    pub todo: Option<TodoConfig>,
+    pub image: Option<ImageConfig>,
+    /// Full screen pictures from a directory instead of the dashboard.
+    pub slideshow: Option<SlideshowConfig>,
+    /// Times the panel shows a sleep screen and is left alone.
+    pub quiet: Vec<QuietPeriod>,
+    pub mqtt: Option<MqttConfig>,
+    pub homeassistant: Option<HomeAssistantConfig>,
+    /// Keep running and redraw periodically instead of drawing once and
+    /// exiting.
+    pub daemon: Option<DaemonConfig>,
+    pub server: ServerConfig,
+    /// Layouts each display cycles through with the next-page button.
+    pub pages: Vec<PageConfig>,
+    /// What each of the board's buttons does, by number. Defaults to next
+    /// page, refresh, toggle news/calendar and acknowledge for 1 to 4.
+    pub buttons: HashMap<String, Action>,
+    /// Several panels sharing the fetched data, in place of the top level
+    /// `panel`, `device`, `bottom` and `clock`.
+    pub displays: Vec<DisplayConfig>,
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Widget {
+    News,
+    Calendar,
+    Todo,
+    HomeAssistant,
+    Image,
+    WorldClock,
+    Countdown,
+}
+
+/// Panel geometry, either a known `model` or explicit dimensions, with any
+/// field overriding the model's value.
+#[derive(Deserialize, Debug, Default, Clone)]
+pub struct PanelConfig {
+    /// `"2.13"` (the default), `"1.54"`, `"2.9"` or `"4.2"`.
+    pub model: Option<String>,
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    /// Clockwise rotation in degrees: 0, 90, 180 or 270.
+    pub rotation: Option<Rotation>,
+    pub mirror_x: Option<bool>,
+    pub mirror_y: Option<bool>,
+    /// `"msb-first"` or `"lsb-first"`.
+    pub bit_order: Option<BitOrder>,
+    /// Black, white and red module.
+    pub tricolor: Option<bool>,
+}
+
+impl PanelConfig {
+    pub fn panel(&self) -> Result<Panel, String> {
+        let base = match &self.model {
+            Some(model) => {
+                Panel::model(model).ok_or_else(|| format!("unknown panel model {:?}", model))?
+            }
+            None => Panel::default(),
+        };
+        Ok(Panel {
+            width: self.width.unwrap_or(base.width),
+            height: self.height.unwrap_or(base.height),
+            rotation: self.rotation.unwrap_or(base.rotation),
+            mirror_x: self.mirror_x.unwrap_or(base.mirror_x),
+            mirror_y: self.mirror_y.unwrap_or(base.mirror_y),
+            bit_order: self.bit_order.unwrap_or(base.bit_order),
+            tricolor: self.tricolor.unwrap_or(base.tricolor),
+        })
+    }
+}
+
+/// How to reach the panel: over TCP or HTTP if `tcp` or `url` is set,
+/// otherwise over serial. Without a `path` the first USB port matching
+/// every id given here is used, so the board is found however it
+/// enumerates.
+#[derive(Deserialize, Debug, Default, Clone)]
+pub struct DeviceConfig {
+    /// `host:port` of a Wi-Fi board listening for raw frames.
+    pub tcp: Option<String>,
+    /// `http://` URL frames are POSTed to.
+    pub url: Option<String>,
+    /// Explicit port such as `/dev/ttyACM0`, skipping the search.
+    pub path: Option<String>,
+    /// USB vendor id.
+    pub vid: Option<u16>,
+    /// USB product id.
+    pub pid: Option<u16>,
+    /// USB serial number, to pick one of several identical boards.
+    pub serial: Option<String>,
+    /// Draw the battery level the board reports in the bottom left corner.
+    #[serde(default)]
+    pub battery: bool,
+}
+
+/// One of several panels, each with its own connection, geometry and
+/// layout. Anything left out is taken from the top level of the config.
+#[derive(Deserialize, Debug, Clone)]
+pub struct DisplayConfig {
+    /// Used in messages and to pick the display on the command line.
+    pub name: String,
+    #[serde(default)]
+    pub panel: PanelConfig,
+    #[serde(default)]
+    pub device: DeviceConfig,
+    pub bottom: Option<Widget>,
+    pub clock: Option<ClockConfig>,
+    /// Minutes between redraws, the daemon's when unset.
+    pub refresh_minutes: Option<u64>,
+}
+
+/// One page of a navigable dashboard, overriding the display's layout.
+#[derive(Deserialize, Debug)]
+pub struct PageConfig {
+    pub bottom: Option<Widget>,
+    pub clock: Option<ClockConfig>,
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum Action {
+    /// Show the display's next page.
+    NextPage,
+    /// Fetch everything again and redraw.
+    Refresh,
+    /// Show news at the bottom, or go back to what it replaced.
+    ToggleNews,
+    /// Stop highlighting calendar events starting soon or under way.
+    Acknowledge,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct CalendarConfig {
+    /// Local `.ics` paths or `http(s)://`/`webcal://` URLs.
+    #[serde(default)]
+    pub sources: Vec<String>,
+    /// CalDAV collections queried for the agenda time range.
+    #[serde(default)]
+    pub caldav: Vec<CalDavSource>,
+    /// How far ahead to look for events.
+    #[serde(default = "default_lookahead_days")]
+    pub lookahead_days: i64,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct CalDavSource {
+    /// URL of the calendar collection, e.g.
+    /// `https://dav.example.com/calendars/alice/team/`.
+    pub url: String,
+    /// Username for HTTP basic auth, the request is sent unauthenticated if
+    /// this is left out.
+    pub username: Option<String>,
+    pub password: Option<String>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct TodoConfig {
+    /// A todo.txt file, or a Markdown checklist if it ends in `.md`.
+    pub path: PathBuf,
+}
+
+/// The font for each text style: `profont-<size>` for one of the built in
+/// ProFont sizes (7, 9, 10, 12, 14, 18 or 24) or the path of a BDF or PCF
+/// font.
+#[derive(Deserialize, Debug, Default)]
+#[serde(rename_all = "kebab-case")]
+pub struct FontsConfig {
+    pub title: Option<String>,
+    pub body: Option<String>,
+    pub caption: Option<String>,
+    pub huge_digit: Option<String>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(default)]
+pub struct LocaleConfig {
+    /// POSIX locale name for day and month names, e.g. `de_DE`.
+    pub language: String,
+    /// IANA zone such as `Europe/Berlin`, the host's zone when unset.
+    pub timezone: Option<String>,
+    /// strftime format for the date line, e.g. `%A %-d %B` or `%Y-%m-%d`.
+    pub date_format: String,
+    /// strftime format for times in the agenda and forecast, e.g. `%I:%M %p`.
+    pub time_format: String,
+}
+
+impl Default for LocaleConfig {
+    fn default() -> Self {
+        LocaleConfig {
+            language: "en_US".to_string(),
+            timezone: None,
+            date_format: "%d/%m/%y".to_string(),
+            time_format: "%H:%M".to_string(),
+        }
+    }
+}
+
+#[derive(Deserialize, Debug)]
+pub struct CountdownConfig {
+    #[serde(default)]
+    pub events: Vec<CountdownEvent>,
+    /// Count down to the next calendar event whose summary contains one of
+    /// these, ignoring case.
+    #[serde(default)]
+    pub calendar: Vec<String>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct CountdownEvent {
+    pub label: String,
+    /// `YYYY-MM-DD` for a whole day or `YYYY-MM-DD HH:MM`, in the
+    /// configured time zone.
+    pub at: String,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct WorldClockConfig {
+    pub zones: Vec<ZoneConfig>,
+    /// Show the zones in place of the date line, in the caption style.
+    #[serde(default)]
+    pub replace_date: bool,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct ZoneConfig {
+    /// Short name such as `NYC`.
+    pub label: String,
+    /// IANA zone such as `America/New_York`.
+    pub timezone: Tz,
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum ClockStyle {
+    /// The `huge-digit` font.
+    Text,
+    /// Classic segments with pointed ends.
+    SevenSegment,
+    /// Segments with rounded ends.
+    Smooth,
+    /// No clock, for displays that only wake when other content changes.
+    Hidden,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct ClockConfig {
+    pub style: ClockStyle,
+    /// 12-hour time with an AM/PM marker.
+    pub hour12: bool,
+    /// Show seconds, redrawing every second in daemon mode. Only worth it
+    /// on panels that support partial refresh.
+    pub seconds: bool,
+    /// Height of the segment digits in pixels.
+    pub digit_height: u32,
+    /// Top left corner.
+    pub position: (i32, i32),
+}
+
+impl Default for ClockConfig {
+    fn default() -> Self {
+        ClockConfig {
+            style: ClockStyle::Text,
+            hour12: false,
+            seconds: false,
+            digit_height: 30,
+            position: (0, 20),
+        }
+    }
+}
+
+/// A PNG, JPEG or BMP file shown in the headline area.
+#[derive(Deserialize, Debug)]
+pub struct ImageConfig {
+    pub path: PathBuf,
+    #[serde(default)]
+    pub fit: Fit,
+    #[serde(default)]
+    pub dither: Dither,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct SlideshowConfig {
+    /// Directory of PNG, JPEG and BMP files, shown in name order.
+    pub directory: PathBuf,
+    /// Minutes each picture stays up.
+    #[serde(default = "default_slide_minutes")]
+    pub interval_minutes: u64,
+    #[serde(default = "default_slide_fit")]
+    pub fit: Fit,
+    #[serde(default)]
+    pub dither: Dither,
+    /// Draw the time in the bottom right corner.
+    #[serde(default)]
+    pub clock: bool,
+    /// Time the slideshow takes over from the dashboard. Without `from`
+    /// and `until` it runs all day.
+    pub from: Option<TimeOfDay>,
+    /// Time the dashboard comes back, may be earlier than `from` to run
+    /// overnight.
+    pub until: Option<TimeOfDay>,
+}
+
+impl SlideshowConfig {
+    /// Whether the slideshow should be showing at `time`.
+    pub fn active(&self, time: NaiveTime) -> bool {
+        match (self.from, self.until) {
+            (Some(from), Some(until)) => from.contains(until, time),
+            (Some(from), None) => time >= from.0,
+            (None, Some(until)) => time < until.0,
+            (None, None) => true,
+        }
+    }
+}
+
+/// A time of day, written `HH:MM`.
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(try_from = "String")]
+pub struct TimeOfDay(pub NaiveTime);
+
+impl TryFrom<String> for TimeOfDay {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        NaiveTime::parse_from_str(&value, "%H:%M")
+            .map(TimeOfDay)
+            .map_err(|e| format!("invalid time {:?}, expected HH:MM: {}", value, e))
+    }
+}
+
+impl TimeOfDay {
+    /// Whether `time` falls between this and `until`, running past midnight
+    /// when `until` is the earlier of the two.
+    pub fn contains(self, until: TimeOfDay, time: NaiveTime) -> bool {
+        if self.0 <= until.0 {
+            self.0 <= time && time < until.0
+        } else {
+            time >= self.0 || time < until.0
+        }
+    }
+
+    /// Seconds from `now` until the clock next reads this time, a whole day
+    /// when it reads it now.
+    pub fn seconds_from(self, now: NaiveTime) -> u64 {
+        let secs = (self.0 - now).num_seconds();
+        if secs > 0 {
+            secs as u64
+        } else {
+            (secs + 24 * 60 * 60) as u64
+        }
+    }
+}
+
+/// A day of the week, written `mon`, `tue`, ... or in full.
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(try_from = "String")]
+pub struct Day(pub Weekday);
+
+impl TryFrom<String> for Day {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value
+            .parse()
+            .map(Day)
+            .map_err(|_| format!("invalid day {:?}", value))
+    }
+}
+
+/// A stretch of time the panel is left alone, see `quiet.rs`. A period
+/// whose `until` is before its `from` runs past midnight into the next day.
+#[derive(Deserialize, Debug)]
+pub struct QuietPeriod {
+    /// Days the period starts on, e.g. `["fri", "sat"]`, every day if empty.
+    #[serde(default)]
+    pub days: Vec<Day>,
+    pub from: TimeOfDay,
+    pub until: TimeOfDay,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct MqttConfig {
+    #[serde(default = "default_mqtt_host")]
+    pub host: String,
+    #[serde(default = "default_mqtt_port")]
+    pub port: u16,
+    #[serde(default = "default_mqtt_client_id")]
+    pub client_id: String,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    /// Readings older than this are drawn as `--`.
+    #[serde(default = "default_stale_minutes")]
+    pub stale_minutes: u64,
+    #[serde(default)]
+    pub sensors: Vec<SensorConfig>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct SensorConfig {
+    /// Exact topic to subscribe to, wildcards aren't supported.
+    pub topic: String,
+    #[serde(default)]
+    pub kind: SensorKind,
+    /// Short prefix drawn before the value.
+    pub label: Option<String>,
+    /// Member to read when the payload is a JSON object rather than a bare
+    /// number, e.g. `temperature` for zigbee2mqtt.
+    pub field: Option<String>,
+    /// Unit appended to `other` readings.
+    pub unit: Option<String>,
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum SensorKind {
+    /// Paired with the outdoor temperature as an in/out comparison.
+    Temperature,
+    Humidity,
+    Co2,
+    #[default]
+    Other,
+}
+
+fn default_mqtt_host() -> String {
+    "localhost".to_string()
+}
+
+fn default_mqtt_port() -> u16 {
+    1883
+}
+
+fn default_mqtt_client_id() -> String {
+    "screen-client".to_string()
+}
+
+fn default_stale_minutes() -> u64 {
+    15
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct HomeAssistantConfig {
+    /// Base URL, e.g. `http://homeassistant.local:8123`.
+    pub url: String,
+    /// A long-lived access token from the user profile page.
+    pub token: String,
+    /// Subscribe to state changes over the websocket API instead of polling
+    /// `/api/states` on every fetch.
+    #[serde(default)]
+    pub websocket: bool,
+    #[serde(default)]
+    pub entities: Vec<EntityConfig>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct EntityConfig {
+    /// Entity id such as `lock.front_door` or `climate.office`.
+    pub id: String,
+    /// Label to draw instead of the entity's friendly name.
+    pub name: Option<String>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct DaemonConfig {
+    /// Minutes between redraws.
+    #[serde(default = "default_refresh_minutes")]
+    pub refresh_minutes: u64,
+    /// Minutes between fetching weather, news and calendars.
+    #[serde(default = "default_fetch_minutes")]
+    pub fetch_minutes: u64,
+}
+
+/// Serving frames over HTTP for displays that poll, see `server.rs`.
+#[derive(Deserialize, Debug)]
+#[serde(default)]
+pub struct ServerConfig {
+    /// Address and port to listen on.
+    pub listen: String,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        ServerConfig {
+            listen: "0.0.0.0:8080".to_string(),
+        }
+    }
+}
+
+fn default_slide_minutes() -> u64 {
+    10
+}
+
+fn default_slide_fit() -> Fit {
+    Fit::Crop
+}
+
+fn default_refresh_minutes() -> u64 {
+    1
+}
+
+pub fn default_fetch_minutes() -> u64 {
+    15
+}
+
+fn default_lookahead_days() -> i64 {
+    7
+}
+
+impl Config {
+    pub fn bottom(&self) -> Widget {
+        match (self.bottom, &self.calendar) {
+            (Some(widget), _) => widget,
+            (None, Some(_)) => Widget::Calendar,
+            (None, None) => Widget::News,
+        }
+    }
+
+    /// Whether any display or page shows `widget` at the bottom.
+    pub fn shows(&self, widget: Widget) -> bool {
+        self.pages.iter().any(|page| page.bottom == Some(widget))
+            || self
+                .displays()
+                .iter()
+                .any(|display| display.bottom.unwrap_or_else(|| self.bottom()) == widget)
+    }
+
+    /// What pressing `button` should do.
+    pub fn action(&self, button: u8) -> Option<Action> {
+        if !self.buttons.is_empty() {
+            return self.buttons.get(&button.to_string()).copied();
+        }
+        match button {
+            1 => Some(Action::NextPage),
+            2 => Some(Action::Refresh),
+            3 => Some(Action::ToggleNews),
+            4 => Some(Action::Acknowledge),
+            _ => None,
+        }
+    }
+
+    /// The configured displays, or a single one made from the top level
+    /// settings.
+    pub fn displays(&self) -> Vec<DisplayConfig> {
+        if !self.displays.is_empty() {
+            return self.displays.clone();
+        }
+        vec![DisplayConfig {
+            name: "panel".to_string(),
+            panel: self.panel.clone(),
+            device: self.device.clone(),
+            bottom: self.bottom,
+            clock: Some(self.clock.clone()),
+            refresh_minutes: None,
+        }]
+    }
+
+    /// Loads the config at `path`, falling back to the defaults if the file
+    /// doesn't exist.
+    pub fn load<P: AsRef<Path>>(
+        path: P,
+    ) -> Result<Config, Box<dyn std::error::Error + Send + Sync>> {
+        match fs::read_to_string(path.as_ref()) {
+            Ok(text) => Ok(toml::from_str(&text)?),
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(Config::default()),
+            Err(e) => Err(e.into()),
+        }
+    }
+}
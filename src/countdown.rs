@@ -0,0 +1,127 @@
+//! Countdowns to dates from the config or events from the calendar.
+
+use crate::{
+    calendar::Event, config::CountdownConfig, fonts::Fonts, locale::Locale, Color, Display,
+};
+use chrono::{DateTime, FixedOffset, NaiveDate, NaiveDateTime, Utc};
+use embedded_graphics::prelude::*;
+
+#[derive(Clone, Debug)]
+pub struct Target {
+    pub label: String,
+    pub start: DateTime<Utc>,
+    /// Until when the target counts as happening `now`.
+    pub end: DateTime<Utc>,
+    /// Counted in days, showing `today` on the day itself.
+    pub all_day: bool,
+}
+
+/// Parses `YYYY-MM-DD` as a whole day or `YYYY-MM-DD HH:MM` as a moment,
+/// both in the configured zone.
+fn parse_at(locale: &Locale, at: &str) -> Option<(DateTime<Utc>, bool)> {
+    if let Ok(date) = NaiveDate::parse_from_str(at, "%Y-%m-%d") {
+        return Some((locale.resolve(date.and_hms_opt(0, 0, 0)?)?, true));
+    }
+    let time = NaiveDateTime::parse_from_str(at, "%Y-%m-%d %H:%M").ok()?;
+    Some((locale.resolve(time)?, false))
+}
+
+/// The configured dates plus the next calendar event matching each
+/// `calendar` pattern, soonest first, leaving out anything already over.
+pub fn targets(
+    config: &CountdownConfig,
+    locale: &Locale,
+    events: Option<&[Event]>,
+    now: DateTime<Utc>,
+) -> Vec<Target> {
+    let mut targets = Vec::new();
+    for event in &config.events {
+        match parse_at(locale, &event.at) {
+            Some((start, all_day)) => targets.push(Target {
+                label: event.label.clone(),
+                start,
+                end: start + chrono::Duration::minutes(if all_day { 24 * 60 } else { 1 }),
+                all_day,
+            }),
+            None => eprintln!("countdown {}: unable to parse {:?}", event.label, event.at),
+        }
+    }
+    for pattern in &config.calendar {
+        let pattern = pattern.to_lowercase();
+        let next = events
+            .unwrap_or_default()
+            .iter()
+            .filter(|e| e.end > now)
+            .find(|e| e.summary.to_lowercase().contains(&pattern));
+        if let Some(event) = next {
+            targets.push(Target {
+                label: event.summary.clone(),
+                start: event.start,
+                end: event.end,
+                all_day: event.all_day,
+            });
+        }
+    }
+    targets.retain(|t| t.end > now);
+    targets.sort_by_key(|t| t.start);
+    targets
+}
+
+/// How long until `target`, in days while it is two or more days off and
+/// in hours and minutes after that.
+fn remaining(target: &Target, locale: &Locale, now: DateTime<FixedOffset>) -> String {
+    let now_utc = now.with_timezone(&Utc);
+    if target.start <= now_utc {
+        return if target.all_day { "today" } else { "now" }.to_string();
+    }
+    if target.all_day {
+        let days = (locale.localize(target.start).date_naive() - now.date_naive()).num_days();
+        return match days {
+            0 => "today".to_string(),
+            1 => "in 1 day".to_string(),
+            d => format!("in {} days", d),
+        };
+    }
+    // round up so the last partial minute still reads `in 1 min`
+    let minutes = ((target.start - now_utc).num_seconds() + 59) / 60;
+    match minutes {
+        m if m >= 2 * 24 * 60 => format!("in {} days", m / (24 * 60)),
+        m if m >= 60 => format!("in {}h {:02}m", m / 60, m % 60),
+        m => format!("in {} min", m),
+    }
+}
+
+/// Draws up to `lines` countdowns, label on the left and time remaining on
+/// the right.
+pub fn draw_countdown(
+    display: &mut Display,
+    fonts: &Fonts,
+    locale: &Locale,
+    targets: &[Target],
+    now: DateTime<FixedOffset>,
+    origin: Coord,
+    lines: usize,
+) {
+    let line_height = fonts.body.height() as i32;
+    let right = display.clip().bottom_right[0] as i32 + 1;
+    for (i, target) in targets.iter().take(lines).enumerate() {
+        let remaining = remaining(target, locale, now);
+        let soon = target.start <= now.with_timezone(&Utc) || remaining.ends_with("min");
+        let fg = if soon { Color::Red } else { Color::Black };
+        let y = origin[1] + i as i32 * line_height;
+        // the label gets whatever the time remaining leaves
+        let remaining_x = right - fonts.body.width(&remaining) as i32;
+        let room = remaining_x - fonts.body.width(" ") as i32 - origin[0];
+        let label = fonts.body.fit(&target.label, room.max(0) as u32);
+        fonts
+            .body
+            .draw(display, &label, Coord::new(origin[0], y), fg, Color::White);
+        fonts.body.draw(
+            display,
+            &remaining,
+            Coord::new(remaining_x, y),
+            fg,
+            Color::White,
+        );
+    }
+}
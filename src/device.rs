@@ -0,0 +1,132 @@
+//! Finding the panel's serial port and keeping it open.
+//!
+//! The board's ACM number changes with the order things are plugged in, so
+//! unless a path is forced the port is looked up by its USB ids each time it
+//! is (re)opened.
+
+use crate::{
+    config::DeviceConfig,
+    push,
+    status::{self, DeviceStatus, Status},
+    transport::Transport,
+};
+use serialport::{SerialPort, SerialPortType};
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+use tokio::sync::mpsc::{self, UnboundedReceiver, UnboundedSender};
+
+type Error = Box<dyn std::error::Error + Send + Sync>;
+
+pub struct Serial {
+    config: DeviceConfig,
+    port: Option<Box<dyn SerialPort>>,
+    status: Status,
+    buttons: UnboundedSender<u8>,
+    /// Presses, until taken by whoever acts on them.
+    presses: Option<UnboundedReceiver<u8>>,
+    /// Cleared to stop the thread reading the current port's messages.
+    listening: Arc<AtomicBool>,
+}
+
+impl Serial {
+    /// `path`, from the command line, overrides the configured one.
+    pub fn new(path: Option<&str>, config: &DeviceConfig) -> Self {
+        let mut config = config.clone();
+        if let Some(path) = path {
+            config.path = Some(path.to_string());
+        }
+        let (buttons, presses) = mpsc::unbounded_channel();
+        Serial {
+            config,
+            port: None,
+            status: Status::default(),
+            buttons,
+            presses: Some(presses),
+            listening: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    /// The configured path, or the first USB port matching the ids.
+    fn find(&self) -> Result<String, Error> {
+        if let Some(path) = &self.config.path {
+            return Ok(path.clone());
+        }
+        let config = &self.config;
+        serialport::available_ports()?
+            .into_iter()
+            .find(|port| match &port.port_type {
+                SerialPortType::UsbPort(usb) => {
+                    config.vid.is_none_or(|vid| vid == usb.vid)
+                        && config.pid.is_none_or(|pid| pid == usb.pid)
+                        && (config.serial.is_none() || config.serial == usb.serial_number)
+                }
+                _ => false,
+            })
+            .map(|port| port.port_name)
+            .ok_or_else(|| {
+                format!(
+                    "no USB serial port found matching vid {} pid {} serial {}, see list-ports",
+                    config
+                        .vid
+                        .map_or("any".to_string(), |vid| format!("{:04x}", vid)),
+                    config
+                        .pid
+                        .map_or("any".to_string(), |pid| format!("{:04x}", pid)),
+                    config.serial.as_deref().unwrap_or("any"),
+                )
+                .into()
+            })
+    }
+
+    fn open(&mut self) -> Result<&mut Box<dyn SerialPort>, Error> {
+        if self.port.is_none() {
+            let path = self.find()?;
+            let port =
+                serialport::open(&path).map_err(|e| format!("unable to open {}: {}", path, e))?;
+            // messages from the board are read on a second handle
+            match port.try_clone() {
+                Ok(mut reader) => {
+                    reader.set_timeout(Duration::from_secs(1))?;
+                    self.listening = Arc::new(AtomicBool::new(true));
+                    status::listen(
+                        path,
+                        reader,
+                        self.status.clone(),
+                        self.buttons.clone(),
+                        self.listening.clone(),
+                    );
+                }
+                Err(e) => eprintln!("{}: not listening for messages: {}", path, e),
+            }
+            self.port = Some(port);
+        }
+        Ok(self.port.as_mut().expect("port was just opened"))
+    }
+}
+
+impl Transport for Serial {
+    /// Sends a frame, opening the port first if needed. A failed write
+    /// closes the port so the next send looks for the device again, which
+    /// picks it back up after it has been unplugged.
+    fn send(&mut self, frame: &[u8]) -> Result<(), Error> {
+        let result = push(self.open()?, frame);
+        if result.is_err() {
+            self.port = None;
+            self.listening.store(false, Ordering::Relaxed);
+        }
+        Ok(result?)
+    }
+
+    fn status(&self) -> Option<DeviceStatus> {
+        Some(self.status.lock().unwrap().clone())
+    }
+
+    fn buttons(&mut self) -> Option<UnboundedReceiver<u8>> {
+        self.presses.take()
+    }
+}
@@ -0,0 +1,324 @@
+//! The framebuffer sent to the panel, and the mapping from drawing
+//! coordinates to bits in the controller's RAM layout.
+//!
+//! Monochrome panels take a single 1-bit plane where a set bit is white.
+//! Tri-color panels take a second plane of the same size straight after it,
+//! where a set bit is red (or yellow, depending on the module).
+
+use embedded_graphics::{
+    drawable::Pixel,
+    prelude::{UnsignedCoord, *},
+    Drawing,
+};
+use image::{Rgb, RgbImage};
+use serde_derive::Deserialize;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Color {
+    Black,
+    White,
+    /// The accent color of a tri-color panel, drawn black on monochrome ones.
+    Red,
+}
+
+impl PixelColor for Color {}
+
+/// Image data only ever holds 0 or 1 per pixel; any other value is drawn
+/// as background rather than panicking mid-draw.
+impl From<u8> for Color {
+    fn from(value: u8) -> Self {
+        match value {
+            0 => Color::Black,
+            2 => Color::Red,
+            _ => Color::White,
+        }
+    }
+}
+
+impl From<u16> for Color {
+    fn from(value: u16) -> Self {
+        match value {
+            0 => Color::Black,
+            2 => Color::Red,
+            _ => Color::White,
+        }
+    }
+}
+
+/// Clockwise rotation of the drawing surface relative to the controller's
+/// native orientation.
+#[derive(Deserialize, Clone, Copy, PartialEq, Debug)]
+#[serde(try_from = "u16")]
+pub enum Rotation {
+    Deg0,
+    Deg90,
+    Deg180,
+    Deg270,
+}
+
+impl std::convert::TryFrom<u16> for Rotation {
+    type Error = String;
+
+    fn try_from(degrees: u16) -> Result<Self, Self::Error> {
+        match degrees {
+            0 => Ok(Rotation::Deg0),
+            90 => Ok(Rotation::Deg90),
+            180 => Ok(Rotation::Deg180),
+            270 => Ok(Rotation::Deg270),
+            _ => Err(format!(
+                "rotation must be 0, 90, 180 or 270, not {}",
+                degrees
+            )),
+        }
+    }
+}
+
+/// Which end of a byte holds the leftmost pixel.
+#[derive(Deserialize, Clone, Copy, PartialEq, Debug)]
+#[serde(rename_all = "kebab-case")]
+pub enum BitOrder {
+    MsbFirst,
+    LsbFirst,
+}
+
+/// Geometry of an e-paper module as its controller sees it.
+///
+/// `width` and `height` are the native RAM dimensions, rows are `width`
+/// pixels packed 8 to a byte and padded to a whole byte. Drawing happens in
+/// the rotated space, so a 128x250 panel rotated by 90° is drawn on as
+/// 250x128.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Panel {
+    pub width: u32,
+    pub height: u32,
+    pub rotation: Rotation,
+    /// Flip along the native x axis, after rotating.
+    pub mirror_x: bool,
+    /// Flip along the native y axis, after rotating.
+    pub mirror_y: bool,
+    pub bit_order: BitOrder,
+    /// Has a red plane as well as the black one.
+    pub tricolor: bool,
+}
+
+impl Default for Panel {
+    /// The Lolin 2.13" shield, in landscape.
+    fn default() -> Self {
+        Panel {
+            width: 128,
+            height: 250,
+            rotation: Rotation::Deg90,
+            mirror_x: false,
+            mirror_y: false,
+            bit_order: BitOrder::MsbFirst,
+            tricolor: false,
+        }
+    }
+}
+
+impl Panel {
+    /// Common modules by their diagonal, all in landscape.
+    pub fn model(name: &str) -> Option<Panel> {
+        let (width, height, rotation) = match name {
+            "2.13" => (128, 250, Rotation::Deg90),
+            "1.54" => (200, 200, Rotation::Deg0),
+            "2.9" => (128, 296, Rotation::Deg90),
+            "4.2" => (400, 300, Rotation::Deg0),
+            _ => return None,
+        };
+        Some(Panel {
+            width,
+            height,
+            rotation,
+            ..Panel::default()
+        })
+    }
+
+    fn stride(&self) -> u32 {
+        self.width.div_ceil(8)
+    }
+
+    /// Size of one color plane in bytes.
+    pub fn plane_len(&self) -> usize {
+        (self.stride() * self.height) as usize
+    }
+
+    /// Size of the framebuffer in bytes, all planes included.
+    pub fn buffer_len(&self) -> usize {
+        self.plane_len() * if self.tricolor { 2 } else { 1 }
+    }
+
+    /// A framebuffer cleared to white.
+    pub fn blank(&self) -> Vec<u8> {
+        let mut buff = vec![0u8; self.buffer_len()];
+        for byte in &mut buff[..self.plane_len()] {
+            *byte = 0xff;
+        }
+        buff
+    }
+
+    /// Width and height of the drawing surface, after rotation.
+    pub fn size(&self) -> (u32, u32) {
+        match self.rotation {
+            Rotation::Deg0 | Rotation::Deg180 => (self.width, self.height),
+            Rotation::Deg90 | Rotation::Deg270 => (self.height, self.width),
+        }
+    }
+
+    /// Byte index and bit mask holding the pixel at `(x, y)`.
+    pub fn locate(&self, x: u32, y: u32) -> (usize, u8) {
+        let (w, h) = (self.width, self.height);
+        let (mut nx, mut ny) = match self.rotation {
+            Rotation::Deg0 => (x, y),
+            Rotation::Deg90 => (y, h - 1 - x),
+            Rotation::Deg180 => (w - 1 - x, h - 1 - y),
+            Rotation::Deg270 => (w - 1 - y, x),
+        };
+        if self.mirror_x {
+            nx = w - 1 - nx;
+        }
+        if self.mirror_y {
+            ny = h - 1 - ny;
+        }
+        let bit = match self.bit_order {
+            BitOrder::MsbFirst => 0x80 >> (nx % 8),
+            BitOrder::LsbFirst => 0x01 << (nx % 8),
+        };
+        ((ny * self.stride() + nx / 8) as usize, bit)
+    }
+
+    /// Reads back the color at `(x, y)` of a drawn frame.
+    pub fn pixel(&self, buff: &[u8], x: u32, y: u32) -> Color {
+        let (index, bit) = self.locate(x, y);
+        if self.tricolor && buff[index + self.plane_len()] & bit != 0 {
+            Color::Red
+        } else if buff[index] & bit != 0 {
+            Color::White
+        } else {
+            Color::Black
+        }
+    }
+
+    /// Renders a frame as it would look on the panel, for previews.
+    pub fn preview(&self, buff: &[u8]) -> RgbImage {
+        let (width, height) = self.size();
+        RgbImage::from_fn(width, height, |x, y| match self.pixel(buff, x, y) {
+            Color::Black => Rgb([0, 0, 0]),
+            Color::White => Rgb([255, 255, 255]),
+            Color::Red => Rgb([200, 0, 0]),
+        })
+    }
+}
+
+/// Inclusive rectangle in drawing coordinates.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Clip {
+    pub top_left: UnsignedCoord,
+    pub bottom_right: UnsignedCoord,
+}
+
+impl Clip {
+    pub fn new(top_left: UnsignedCoord, bottom_right: UnsignedCoord) -> Self {
+        Clip {
+            top_left,
+            bottom_right,
+        }
+    }
+
+    fn contains(&self, x: u32, y: u32) -> bool {
+        (self.top_left[0]..=self.bottom_right[0]).contains(&x)
+            && (self.top_left[1]..=self.bottom_right[1]).contains(&y)
+    }
+}
+
+/// A framebuffer being drawn on.
+///
+/// Pixels outside the panel, or outside the current clip rectangle, are
+/// dropped, so text and images can run off the edge of their area.
+pub struct Display<'a> {
+    pub buff: &'a mut [u8],
+    pub panel: Panel,
+    clip: Option<Clip>,
+}
+
+impl<'a> Display<'a> {
+    pub fn new(buff: &'a mut [u8], panel: Panel) -> Self {
+        Display {
+            buff,
+            panel,
+            clip: None,
+        }
+    }
+
+    /// The area drawing is currently limited to, the whole panel outside
+    /// of [`Display::clipped`].
+    pub fn clip(&self) -> Clip {
+        let (width, height) = self.panel.size();
+        self.clip.unwrap_or_else(|| {
+            Clip::new(
+                UnsignedCoord::new(0, 0),
+                UnsignedCoord::new(width - 1, height - 1),
+            )
+        })
+    }
+
+    /// Runs `draw` with drawing limited to `clip`, within any clip already
+    /// in effect.
+    pub fn clipped<F>(&mut self, clip: Clip, draw: F)
+    where
+        F: FnOnce(&mut Self),
+    {
+        let outer = self.clip;
+        self.clip = Some(match outer {
+            Some(outer) => Clip::new(
+                UnsignedCoord::new(
+                    clip.top_left[0].max(outer.top_left[0]),
+                    clip.top_left[1].max(outer.top_left[1]),
+                ),
+                UnsignedCoord::new(
+                    clip.bottom_right[0].min(outer.bottom_right[0]),
+                    clip.bottom_right[1].min(outer.bottom_right[1]),
+                ),
+            ),
+            None => clip,
+        });
+        draw(self);
+        self.clip = outer;
+    }
+
+    fn set_pixel(&mut self, x: u32, y: u32, color: Color) {
+        let (width, height) = self.panel.size();
+        if x >= width || y >= height || self.clip.is_some_and(|c| !c.contains(x, y)) {
+            return;
+        }
+        let (index, bit) = self.panel.locate(x, y);
+        let red = self.panel.tricolor && color == Color::Red;
+
+        // red shows through a white pixel on the black plane
+        match color {
+            Color::Black => self.buff[index] &= !bit,
+            Color::White => self.buff[index] |= bit,
+            Color::Red if red => self.buff[index] |= bit,
+            Color::Red => self.buff[index] &= !bit,
+        }
+        if self.panel.tricolor {
+            let index = index + self.panel.plane_len();
+            if red {
+                self.buff[index] |= bit;
+            } else {
+                self.buff[index] &= !bit;
+            }
+        }
+    }
+}
+
+impl<'a> Drawing<Color> for Display<'a> {
+    fn draw<T>(&mut self, item_pixels: T)
+    where
+        T: IntoIterator<Item = Pixel<Color>>,
+    {
+        for Pixel(UnsignedCoord(x, y), colour) in item_pixels {
+            self.set_pixel(x, y, colour);
+        }
+    }
+}
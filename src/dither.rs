@@ -0,0 +1,173 @@
+//! Loading PNG, JPEG and BMP files onto the 1-bit panel.
+//!
+//! Images are converted to grayscale, scaled to the area they will occupy
+//! and then dithered, so photos keep some of their shading instead of being
+//! cut at a flat threshold.
+
+use crate::{Color, Display};
+use embedded_graphics::{drawable::Pixel, prelude::*, Drawing};
+use image::{imageops::FilterType, GrayImage, Luma};
+use serde_derive::Deserialize;
+use std::path::Path;
+
+#[derive(Deserialize, Debug, Default, Clone, Copy, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum Dither {
+    /// Error diffusion, best for photos.
+    #[default]
+    FloydSteinberg,
+    /// 8x8 Bayer matrix, a regular pattern that suits flat areas and logos.
+    Ordered,
+    /// Plain 50% cut-off, for images that are already black and white.
+    Threshold,
+}
+
+/// How an image is fitted into an area with a different aspect ratio.
+#[derive(Deserialize, Debug, Default, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Fit {
+    /// Scale to cover the whole area, cropping the overflow evenly.
+    Crop,
+    /// Scale to fit inside the area, padding with white bars.
+    #[default]
+    Letterbox,
+    /// Scale each axis independently.
+    Stretch,
+}
+
+const BAYER: [[u8; 8]; 8] = [
+    [0, 32, 8, 40, 2, 34, 10, 42],
+    [48, 16, 56, 24, 50, 18, 58, 26],
+    [12, 44, 4, 36, 14, 46, 6, 38],
+    [60, 28, 52, 20, 62, 30, 54, 22],
+    [3, 35, 11, 43, 1, 33, 9, 41],
+    [51, 19, 59, 27, 49, 17, 57, 25],
+    [15, 47, 7, 39, 13, 45, 5, 37],
+    [63, 31, 55, 23, 61, 29, 53, 21],
+];
+
+/// A 1-bit image ready to be drawn.
+#[derive(Clone, Debug)]
+pub struct Bitmap {
+    pub width: u32,
+    pub height: u32,
+    /// Row-major, `true` is black.
+    pub pixels: Vec<bool>,
+}
+
+impl Bitmap {
+    /// Reduces a grayscale image to black and white.
+    pub fn dither(image: &GrayImage, method: Dither) -> Self {
+        let (width, height) = image.dimensions();
+        let pixels = match method {
+            Dither::FloydSteinberg => floyd_steinberg(image),
+            Dither::Ordered => image
+                .enumerate_pixels()
+                .map(|(x, y, Luma([v]))| {
+                    let threshold = BAYER[y as usize % 8][x as usize % 8] as u16 * 4 + 2;
+                    u16::from(*v) < threshold
+                })
+                .collect(),
+            Dither::Threshold => image.pixels().map(|Luma([v])| *v < 128).collect(),
+        };
+        Bitmap {
+            width,
+            height,
+            pixels,
+        }
+    }
+
+    /// Draws the bitmap with its top left corner at `origin`, white pixels
+    /// included so it covers whatever was underneath.
+    pub fn draw(&self, display: &mut Display, origin: Coord) {
+        let pixels = (0..self.height)
+            .flat_map(|y| (0..self.width).map(move |x| (x, y)))
+            .filter_map(|(x, y)| {
+                let p = origin + Coord::new(x as i32, y as i32);
+                if p[0] < 0 || p[1] < 0 {
+                    return None;
+                }
+                let black = self.pixels[(y * self.width + x) as usize];
+                let color = if black { Color::Black } else { Color::White };
+                Some(Pixel(p.to_unsigned(), color))
+            });
+        display.draw(pixels);
+    }
+}
+
+fn floyd_steinberg(image: &GrayImage) -> Vec<bool> {
+    let (width, height) = (image.width() as usize, image.height() as usize);
+    let mut levels: Vec<f32> = image.pixels().map(|Luma([v])| f32::from(*v)).collect();
+    let mut black = vec![false; levels.len()];
+    for y in 0..height {
+        for x in 0..width {
+            let i = y * width + x;
+            let old = levels[i];
+            let new = if old < 128.0 { 0.0 } else { 255.0 };
+            black[i] = new == 0.0;
+            let error = old - new;
+            if x + 1 < width {
+                levels[i + 1] += error * 7.0 / 16.0;
+            }
+            if y + 1 < height {
+                if x > 0 {
+                    levels[i + width - 1] += error * 3.0 / 16.0;
+                }
+                levels[i + width] += error * 5.0 / 16.0;
+                if x + 1 < width {
+                    levels[i + width + 1] += error / 16.0;
+                }
+            }
+        }
+    }
+    black
+}
+
+/// Scales a grayscale image to exactly `width` by `height`.
+pub fn fit(image: &GrayImage, width: u32, height: u32, fit: Fit) -> GrayImage {
+    let filter = FilterType::Triangle;
+    let (w, h) = (image.width().max(1) as f64, image.height().max(1) as f64);
+    match fit {
+        Fit::Stretch => image::imageops::resize(image, width, height, filter),
+        Fit::Crop => {
+            let scale = (width as f64 / w).max(height as f64 / h);
+            let (sw, sh) = (
+                ((w * scale).round() as u32).max(width),
+                ((h * scale).round() as u32).max(height),
+            );
+            let mut scaled = image::imageops::resize(image, sw, sh, filter);
+            image::imageops::crop(
+                &mut scaled,
+                (sw - width) / 2,
+                (sh - height) / 2,
+                width,
+                height,
+            )
+            .to_image()
+        }
+        Fit::Letterbox => {
+            let scale = (width as f64 / w).min(height as f64 / h);
+            let (sw, sh) = (
+                ((w * scale).round() as u32).clamp(1, width),
+                ((h * scale).round() as u32).clamp(1, height),
+            );
+            let scaled = image::imageops::resize(image, sw, sh, filter);
+            let mut out = GrayImage::from_pixel(width, height, Luma([255]));
+            image::imageops::overlay(&mut out, &scaled, (width - sw) / 2, (height - sh) / 2);
+            out
+        }
+    }
+}
+
+/// Opens a PNG, JPEG or BMP file and turns it into a `width` by `height`
+/// bitmap.
+pub fn load(
+    path: &Path,
+    width: u32,
+    height: u32,
+    fit_to: Fit,
+    method: Dither,
+) -> image::ImageResult<Bitmap> {
+    let image = image::open(path)?.into_luma8();
+    Ok(Bitmap::dither(&fit(&image, width, height, fit_to), method))
+}
@@ -0,0 +1,546 @@
+//! Text styles and the fonts behind them.
+//!
+//! Widgets draw text in one of four named styles instead of picking a font
+//! themselves. Each style is one of the built in ProFont sizes or a BDF or
+//! PCF bitmap font loaded at startup, so headlines in other languages only
+//! need a font with the right glyphs, e.g. one of the ISO10646-1 encoded
+//! misc-fixed fonts. PCF files must be uncompressed.
+
+use crate::{config::FontsConfig, Color, Display};
+use embedded_graphics::{drawable::Pixel, fonts::Font, prelude::*, primitives::Rectangle, Drawing};
+use profont::{
+    ProFont10Point, ProFont12Point, ProFont14Point, ProFont18Point, ProFont24Point, ProFont7Point,
+    ProFont9Point,
+};
+use std::{collections::HashMap, convert::TryFrom, path::Path, sync::Arc};
+
+type Error = Box<dyn std::error::Error + Send + Sync>;
+
+/// One glyph of a bitmap font, positioned relative to the pen on the
+/// baseline.
+#[derive(Clone, Debug)]
+struct Glyph {
+    width: u32,
+    height: u32,
+    x_offset: i32,
+    /// Distance from the baseline up to the top row.
+    ascent: i32,
+    advance: i32,
+    /// `height` rows of `(width + 7) / 8` bytes, leftmost pixel in the top
+    /// bit.
+    bitmap: Vec<u8>,
+}
+
+impl Glyph {
+    fn is_set(&self, x: u32, y: u32) -> bool {
+        let stride = self.width.div_ceil(8);
+        self.bitmap[(y * stride + x / 8) as usize] & (0x80 >> (x % 8)) != 0
+    }
+}
+
+/// A BDF or PCF font, indexed by Unicode code point.
+#[derive(Debug)]
+pub struct BitmapFont {
+    glyphs: HashMap<char, Glyph>,
+    default: Option<char>,
+    ascent: i32,
+    descent: i32,
+}
+
+impl BitmapFont {
+    fn glyph(&self, c: char) -> Option<&Glyph> {
+        self.glyphs
+            .get(&c)
+            .or_else(|| self.default.and_then(|d| self.glyphs.get(&d)))
+            .or_else(|| self.glyphs.get(&'?'))
+    }
+
+    pub fn load(path: &Path) -> Result<Self, Error> {
+        let data = std::fs::read(path)?;
+        if data.starts_with(b"\x01fcp") {
+            parse_pcf(&data)
+        } else if data.starts_with(b"STARTFONT") {
+            parse_bdf(&String::from_utf8_lossy(&data))
+        } else {
+            Err("not a BDF or uncompressed PCF font".into())
+        }
+    }
+}
+
+fn parse_bdf(text: &str) -> Result<BitmapFont, Error> {
+    let mut glyphs = HashMap::new();
+    let mut default = None;
+    let (mut ascent, mut descent) = (None, None);
+    let mut bbox = (0u32, 0u32, 0i32, 0i32);
+
+    let mut lines = text.lines();
+    while let Some(line) = lines.next() {
+        let mut words = line.split_whitespace();
+        match words.next() {
+            Some("FONTBOUNDINGBOX") => {
+                let n: Vec<i32> = words.filter_map(|w| w.parse().ok()).collect();
+                if let [w, h, x, y] = n[..] {
+                    bbox = (w as u32, h as u32, x, y);
+                }
+            }
+            Some("FONT_ASCENT") => ascent = words.next().and_then(|w| w.parse().ok()),
+            Some("FONT_DESCENT") => descent = words.next().and_then(|w| w.parse().ok()),
+            Some("DEFAULT_CHAR") => {
+                default = words
+                    .next()
+                    .and_then(|w| w.parse().ok())
+                    .and_then(std::char::from_u32)
+            }
+            Some("STARTCHAR") => {
+                let mut code = None;
+                let (mut width, mut height, mut x_offset, mut y_offset) = bbox;
+                let mut advance = bbox.0 as i32;
+                let mut bitmap = Vec::new();
+                for line in lines.by_ref() {
+                    let mut words = line.split_whitespace();
+                    match words.next() {
+                        Some("ENCODING") => code = words.next().and_then(|w| w.parse::<i64>().ok()),
+                        Some("DWIDTH") => {
+                            advance = words.next().and_then(|w| w.parse().ok()).unwrap_or(advance)
+                        }
+                        Some("BBX") => {
+                            let n: Vec<i32> = words.filter_map(|w| w.parse().ok()).collect();
+                            if let [w, h, x, y] = n[..] {
+                                width = w.max(0) as u32;
+                                height = h.max(0) as u32;
+                                x_offset = x;
+                                y_offset = y;
+                            }
+                        }
+                        Some("BITMAP") => {}
+                        Some("ENDCHAR") => break,
+                        Some(hex) if hex.chars().all(|c| c.is_ascii_hexdigit()) => {
+                            let stride = width.div_ceil(8) as usize;
+                            let mut row: Vec<u8> = (0..hex.len() / 2)
+                                .filter_map(|i| u8::from_str_radix(&hex[i * 2..i * 2 + 2], 16).ok())
+                                .collect();
+                            row.resize(stride, 0);
+                            bitmap.extend(row);
+                        }
+                        _ => {}
+                    }
+                }
+                bitmap.resize((width.div_ceil(8) * height) as usize, 0);
+                // ENCODING -1 marks glyphs without a code point
+                if let Some(c) = code
+                    .and_then(|c| u32::try_from(c).ok())
+                    .and_then(std::char::from_u32)
+                {
+                    glyphs.insert(
+                        c,
+                        Glyph {
+                            width,
+                            height,
+                            x_offset,
+                            ascent: y_offset + height as i32,
+                            advance,
+                            bitmap,
+                        },
+                    );
+                }
+            }
+            _ => {}
+        }
+    }
+    if glyphs.is_empty() {
+        return Err("no glyphs in BDF font".into());
+    }
+    Ok(BitmapFont {
+        glyphs,
+        default,
+        ascent: ascent.unwrap_or(bbox.1 as i32 + bbox.3),
+        descent: descent.unwrap_or(-bbox.3),
+    })
+}
+
+const PCF_ACCELERATORS: u32 = 1 << 1;
+const PCF_METRICS: u32 = 1 << 2;
+const PCF_BITMAPS: u32 = 1 << 3;
+const PCF_BDF_ENCODINGS: u32 = 1 << 5;
+const PCF_BDF_ACCELERATORS: u32 = 1 << 8;
+
+const PCF_BYTE_MSB: u32 = 1 << 2;
+const PCF_BIT_MSB: u32 = 1 << 3;
+const PCF_COMPRESSED_METRICS: u32 = 0x100;
+
+/// Cursor over one PCF table, honouring the table's byte order.
+struct PcfReader<'a> {
+    data: &'a [u8],
+    pos: usize,
+    big_endian: bool,
+}
+
+impl<'a> PcfReader<'a> {
+    fn bytes(&mut self, n: usize) -> Result<&'a [u8], Error> {
+        let bytes = self
+            .data
+            .get(self.pos..self.pos + n)
+            .ok_or("truncated PCF font")?;
+        self.pos += n;
+        Ok(bytes)
+    }
+
+    fn u8(&mut self) -> Result<u8, Error> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16, Error> {
+        let b = self.bytes(2)?;
+        Ok(if self.big_endian {
+            u16::from_be_bytes([b[0], b[1]])
+        } else {
+            u16::from_le_bytes([b[0], b[1]])
+        })
+    }
+
+    fn i16(&mut self) -> Result<i16, Error> {
+        Ok(self.u16()? as i16)
+    }
+
+    fn u32(&mut self) -> Result<u32, Error> {
+        let b = self.bytes(4)?;
+        let b = [b[0], b[1], b[2], b[3]];
+        Ok(if self.big_endian {
+            u32::from_be_bytes(b)
+        } else {
+            u32::from_le_bytes(b)
+        })
+    }
+
+    fn i32(&mut self) -> Result<i32, Error> {
+        Ok(self.u32()? as i32)
+    }
+}
+
+/// Opens the table of the given type, returning its format and a reader
+/// positioned after the format word.
+fn pcf_table(data: &[u8], kind: u32) -> Result<Option<(u32, PcfReader<'_>)>, Error> {
+    let mut toc = PcfReader {
+        data,
+        pos: 4,
+        big_endian: false,
+    };
+    let count = toc.u32()?;
+    for _ in 0..count {
+        let (table, _format, _size, offset) = (toc.u32()?, toc.u32()?, toc.u32()?, toc.u32()?);
+        if table == kind {
+            let mut reader = PcfReader {
+                data,
+                pos: offset as usize,
+                big_endian: false,
+            };
+            let format = reader.u32()?;
+            reader.big_endian = format & PCF_BYTE_MSB != 0;
+            return Ok(Some((format, reader)));
+        }
+    }
+    Ok(None)
+}
+
+struct PcfMetrics {
+    left: i32,
+    right: i32,
+    advance: i32,
+    ascent: i32,
+    descent: i32,
+}
+
+fn parse_pcf(data: &[u8]) -> Result<BitmapFont, Error> {
+    let (format, mut r) = pcf_table(data, PCF_METRICS)?.ok_or("PCF font has no metrics")?;
+    let metrics = if format & PCF_COMPRESSED_METRICS != 0 {
+        let count = r.u16()?;
+        (0..count)
+            .map(|_| {
+                let mut next = || r.u8().map(|b| i32::from(b) - 0x80);
+                Ok(PcfMetrics {
+                    left: next()?,
+                    right: next()?,
+                    advance: next()?,
+                    ascent: next()?,
+                    descent: next()?,
+                })
+            })
+            .collect::<Result<Vec<_>, Error>>()?
+    } else {
+        let count = r.u32()?;
+        (0..count)
+            .map(|_| {
+                let m = PcfMetrics {
+                    left: r.i16()?.into(),
+                    right: r.i16()?.into(),
+                    advance: r.i16()?.into(),
+                    ascent: r.i16()?.into(),
+                    descent: r.i16()?.into(),
+                };
+                r.u16()?; // attributes
+                Ok(m)
+            })
+            .collect::<Result<Vec<_>, Error>>()?
+    };
+
+    let (format, mut r) = pcf_table(data, PCF_BITMAPS)?.ok_or("PCF font has no bitmaps")?;
+    let count = r.u32()? as usize;
+    let offsets = (0..count)
+        .map(|_| r.u32())
+        .collect::<Result<Vec<_>, Error>>()?;
+    let sizes = [r.u32()?, r.u32()?, r.u32()?, r.u32()?];
+    let pad = 1usize << (format & 3);
+    let bits = r.bytes(sizes[(format & 3) as usize] as usize)?;
+    // scan units are swapped when the byte and bit order disagree
+    let unit = 1usize << ((format >> 4) & 3);
+    let swap = (format & PCF_BYTE_MSB != 0) != (format & PCF_BIT_MSB != 0) && unit > 1;
+    let lsb_bits = format & PCF_BIT_MSB == 0;
+
+    let (_, mut r) = pcf_table(data, PCF_BDF_ENCODINGS)?.ok_or("PCF font has no encodings")?;
+    let (min2, max2, min1, max1) = (r.u16()?, r.u16()?, r.u16()?, r.u16()?);
+    let default_char = r.u16()?;
+    let mut glyphs = HashMap::new();
+    for byte1 in min1..=max1 {
+        for byte2 in min2..=max2 {
+            let index = r.u16()? as usize;
+            if index == 0xffff || index >= count || index >= metrics.len() {
+                continue;
+            }
+            let code = u32::from(byte1) << 8 | u32::from(byte2);
+            let c = match std::char::from_u32(code) {
+                Some(c) => c,
+                None => continue,
+            };
+            let m = &metrics[index];
+            let width = (m.right - m.left).max(0) as u32;
+            let height = (m.ascent + m.descent).max(0) as u32;
+            let src_stride = width.div_ceil(8) as usize;
+            let src_stride = src_stride.div_ceil(pad) * pad;
+            let start = offsets[index] as usize;
+            let mut bitmap = Vec::with_capacity(width.div_ceil(8) as usize * height as usize);
+            for row in 0..height as usize {
+                let mut line = bits
+                    .get(start + row * src_stride..start + (row + 1) * src_stride)
+                    .ok_or("PCF glyph outside the bitmap table")?
+                    .to_vec();
+                if swap {
+                    for chunk in line.chunks_mut(unit) {
+                        chunk.reverse();
+                    }
+                }
+                if lsb_bits {
+                    for byte in &mut line {
+                        *byte = byte.reverse_bits();
+                    }
+                }
+                bitmap.extend_from_slice(&line[..width.div_ceil(8) as usize]);
+            }
+            glyphs.insert(
+                c,
+                Glyph {
+                    width,
+                    height,
+                    x_offset: m.left,
+                    ascent: m.ascent,
+                    advance: m.advance,
+                    bitmap,
+                },
+            );
+        }
+    }
+    if glyphs.is_empty() {
+        return Err("no glyphs in PCF font".into());
+    }
+
+    let accelerators = match pcf_table(data, PCF_BDF_ACCELERATORS)? {
+        Some(table) => Some(table),
+        None => pcf_table(data, PCF_ACCELERATORS)?,
+    };
+    let (ascent, descent) = match accelerators {
+        Some((_, mut r)) => {
+            // eight flag bytes precede the font ascent and descent
+            r.bytes(8)?;
+            (r.i32()?, r.i32()?)
+        }
+        None => (
+            metrics.iter().map(|m| m.ascent).max().unwrap_or(0),
+            metrics.iter().map(|m| m.descent).max().unwrap_or(0),
+        ),
+    };
+    Ok(BitmapFont {
+        glyphs,
+        default: std::char::from_u32(u32::from(default_char)),
+        ascent,
+        descent,
+    })
+}
+
+/// A font a text style can use.
+#[derive(Clone, Debug)]
+pub enum Face {
+    ProFont7,
+    ProFont9,
+    ProFont10,
+    ProFont12,
+    ProFont14,
+    ProFont18,
+    ProFont24,
+    Bitmap(Arc<BitmapFont>),
+}
+
+impl Face {
+    /// Parses `profont-<size>` or the path of a `.bdf` or `.pcf` file.
+    pub fn load(spec: &str) -> Result<Face, Error> {
+        Ok(match spec {
+            "profont-7" => Face::ProFont7,
+            "profont-9" => Face::ProFont9,
+            "profont-10" => Face::ProFont10,
+            "profont-12" => Face::ProFont12,
+            "profont-14" => Face::ProFont14,
+            "profont-18" => Face::ProFont18,
+            "profont-24" => Face::ProFont24,
+            path => Face::Bitmap(Arc::new(
+                BitmapFont::load(Path::new(path)).map_err(|e| format!("{}: {}", path, e))?,
+            )),
+        })
+    }
+
+    /// Height of a line of text.
+    pub fn height(&self) -> u32 {
+        match self {
+            Face::ProFont7 => 9,
+            Face::ProFont9 => 11,
+            Face::ProFont10 => 13,
+            Face::ProFont12 => 15,
+            Face::ProFont14 => 18,
+            Face::ProFont18 => 22,
+            Face::ProFont24 => 30,
+            Face::Bitmap(font) => (font.ascent + font.descent).max(0) as u32,
+        }
+    }
+
+    /// Width `text` takes up when drawn.
+    pub fn width(&self, text: &str) -> u32 {
+        let chars = text.chars().count() as u32;
+        match self {
+            Face::ProFont7 => chars * 5,
+            Face::ProFont9 => chars * 6,
+            Face::ProFont10 => chars * 7,
+            Face::ProFont12 => chars * 8,
+            Face::ProFont14 => chars * 10,
+            Face::ProFont18 => chars * 12,
+            Face::ProFont24 => chars * 16,
+            Face::Bitmap(font) => text
+                .chars()
+                .filter_map(|c| font.glyph(c))
+                .map(|g| g.advance.max(0) as u32)
+                .sum(),
+        }
+    }
+
+    /// The longest start of `text` that fits in `width` pixels.
+    pub fn fit(&self, text: &str, width: u32) -> String {
+        let mut used = 0;
+        text.chars()
+            .take_while(|c| {
+                used += self.width(c.encode_utf8(&mut [0; 4]));
+                used <= width
+            })
+            .collect()
+    }
+
+    /// Roughly how many characters fit in `width` pixels, for wrapping.
+    pub fn columns(&self, width: u32) -> usize {
+        (width / self.width("m").max(1)).max(1) as usize
+    }
+
+    /// Draws `text` with its top left corner at `origin`, filling the line
+    /// behind it with `bg`.
+    pub fn draw(&self, display: &mut Display, text: &str, origin: Coord, fg: Color, bg: Color) {
+        macro_rules! profont {
+            ($font:ident) => {
+                display.draw(
+                    $font::render_str(text)
+                        .stroke(Some(fg))
+                        .fill(Some(bg))
+                        .translate(origin),
+                )
+            };
+        }
+        match self {
+            Face::ProFont7 => profont!(ProFont7Point),
+            Face::ProFont9 => profont!(ProFont9Point),
+            Face::ProFont10 => profont!(ProFont10Point),
+            Face::ProFont12 => profont!(ProFont12Point),
+            Face::ProFont14 => profont!(ProFont14Point),
+            Face::ProFont18 => profont!(ProFont18Point),
+            Face::ProFont24 => profont!(ProFont24Point),
+            Face::Bitmap(font) => {
+                let width = self.width(text) as i32;
+                if width > 0 {
+                    display.draw(
+                        Rectangle::new(
+                            origin,
+                            origin + Coord::new(width - 1, self.height() as i32 - 1),
+                        )
+                        .fill(Some(bg)),
+                    );
+                }
+                let baseline = origin[1] + font.ascent;
+                let mut pen = origin[0];
+                for glyph in text.chars().filter_map(|c| font.glyph(c)) {
+                    let left = pen + glyph.x_offset;
+                    let top = baseline - glyph.ascent;
+                    let pixels = (0..glyph.height)
+                        .flat_map(|y| (0..glyph.width).map(move |x| (x, y)))
+                        .filter(|&(x, y)| glyph.is_set(x, y))
+                        .map(|(x, y)| Coord::new(left + x as i32, top + y as i32))
+                        .filter(|p| p[0] >= 0 && p[1] >= 0)
+                        .map(|p| Pixel(p.to_unsigned(), fg));
+                    display.draw(pixels);
+                    pen += glyph.advance;
+                }
+            }
+        }
+    }
+}
+
+/// The fonts behind each named text style.
+#[derive(Clone, Debug)]
+pub struct Fonts {
+    /// The date and current conditions.
+    pub title: Face,
+    /// Headlines, agenda, lists and forecasts.
+    pub body: Face,
+    /// Small print.
+    pub caption: Face,
+    /// The clock.
+    pub huge: Face,
+}
+
+impl Default for Fonts {
+    fn default() -> Self {
+        Fonts {
+            title: Face::ProFont14,
+            body: Face::ProFont9,
+            caption: Face::ProFont7,
+            huge: Face::ProFont24,
+        }
+    }
+}
+
+impl Fonts {
+    pub fn load(config: &FontsConfig) -> Result<Self, Error> {
+        let defaults = Fonts::default();
+        let face = |spec: &Option<String>, default: Face| match spec {
+            Some(spec) => Face::load(spec),
+            None => Ok(default),
+        };
+        Ok(Fonts {
+            title: face(&config.title, defaults.title)?,
+            body: face(&config.body, defaults.body)?,
+            caption: face(&config.caption, defaults.caption)?,
+            huge: face(&config.huge_digit, defaults.huge)?,
+        })
+    }
+}
@@ -0,0 +1,465 @@
+//! Home Assistant entity states, polled over the REST API or pushed over the
+//! websocket API.
+
+use crate::{
+    config::{EntityConfig, HomeAssistantConfig},
+    fonts::Fonts,
+    Color, Display, HttpsClient,
+};
+use embedded_graphics::{prelude::*, Drawing};
+use futures::{SinkExt, StreamExt};
+use hyper::{header, Body, Request};
+use serde_derive::Deserialize;
+use serde_json::{json, Map, Value};
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+use tokio::time::delay_for;
+use tokio_tungstenite::{connect_async, tungstenite::Message};
+
+const RECONNECT_DELAY: Duration = Duration::from_secs(30);
+
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+pub struct State {
+    pub entity_id: String,
+    pub state: String,
+    #[serde(default)]
+    pub attributes: Map<String, Value>,
+}
+
+/// Latest state per entity id, only holding the configured entities.
+pub type States = Arc<Mutex<HashMap<String, State>>>;
+
+fn store(config: &HomeAssistantConfig, states: &States, state: State) {
+    if config.entities.iter().any(|e| e.id == state.entity_id) {
+        states
+            .lock()
+            .unwrap()
+            .insert(state.entity_id.clone(), state);
+    }
+}
+
+/// Fetches every state from `/api/states` and keeps the configured ones.
+pub async fn poll(
+    client: &HttpsClient,
+    config: &HomeAssistantConfig,
+    states: &States,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let req = Request::get(format!("{}/api/states", config.url.trim_end_matches('/')))
+        .header(header::AUTHORIZATION, format!("Bearer {}", config.token))
+        .body(Body::empty())?;
+    let resp = client.request(req).await?;
+    if !resp.status().is_success() {
+        return Err(format!("{} returned {}", config.url, resp.status()).into());
+    }
+    let body = hyper::body::to_bytes(resp.into_body()).await?;
+    for state in serde_json::from_slice::<Vec<State>>(&body)? {
+        store(config, states, state);
+    }
+    Ok(())
+}
+
+fn websocket_url(url: &str) -> String {
+    let url = url.trim_end_matches('/');
+    let url = if let Some(rest) = url.strip_prefix("https://") {
+        format!("wss://{}", rest)
+    } else if let Some(rest) = url.strip_prefix("http://") {
+        format!("ws://{}", rest)
+    } else {
+        url.to_string()
+    };
+    format!("{}/api/websocket", url)
+}
+
+async fn session(
+    config: &HomeAssistantConfig,
+    states: &States,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let (mut ws, _) = connect_async(websocket_url(&config.url).as_str()).await?;
+    while let Some(msg) = ws.next().await {
+        let text = match msg? {
+            Message::Text(text) => text,
+            Message::Close(_) => break,
+            _ => continue,
+        };
+        let msg: Value = serde_json::from_str(&text)?;
+        match msg["type"].as_str() {
+            Some("auth_required") => {
+                let auth = json!({"type": "auth", "access_token": config.token});
+                ws.send(Message::Text(auth.to_string())).await?;
+            }
+            Some("auth_ok") => {
+                let get = json!({"id": 1, "type": "get_states"});
+                ws.send(Message::Text(get.to_string())).await?;
+                let subscribe =
+                    json!({"id": 2, "type": "subscribe_events", "event_type": "state_changed"});
+                ws.send(Message::Text(subscribe.to_string())).await?;
+            }
+            Some("auth_invalid") => return Err("access token rejected".into()),
+            Some("result") if msg["id"] == 1 => {
+                let all: Vec<State> = serde_json::from_value(msg["result"].clone())?;
+                for state in all {
+                    store(config, states, state);
+                }
+            }
+            Some("event") => {
+                let new_state = &msg["event"]["data"]["new_state"];
+                if let Ok(state) = serde_json::from_value(new_state.clone()) {
+                    store(config, states, state);
+                }
+            }
+            _ => {}
+        }
+    }
+    Err("connection closed".into())
+}
+
+/// Starts a background task holding a websocket subscription to
+/// `state_changed` events, reconnecting whenever it drops.
+pub fn subscribe(config: HomeAssistantConfig, states: States) {
+    tokio::spawn(async move {
+        loop {
+            if let Err(e) = session(&config, &states).await {
+                eprintln!("home assistant {}: {}", config.url, e);
+            }
+            delay_for(RECONNECT_DELAY).await;
+        }
+    });
+}
+
+// 8x8 icons, one byte per row, set bits are drawn black
+const LOCKED: [u8; 8] = [
+    0b0011_1100,
+    0b0100_0010,
+    0b0100_0010,
+    0b1111_1111,
+    0b1111_1111,
+    0b1110_0111,
+    0b1111_1111,
+    0b1111_1111,
+];
+const UNLOCKED: [u8; 8] = [
+    0b0011_1100,
+    0b0100_0010,
+    0b0100_0000,
+    0b1111_1111,
+    0b1111_1111,
+    0b1110_0111,
+    0b1111_1111,
+    0b1111_1111,
+];
+const BULB_ON: [u8; 8] = [
+    0b0011_1100,
+    0b0111_1110,
+    0b1111_1111,
+    0b1111_1111,
+    0b0111_1110,
+    0b0011_1100,
+    0b0011_1100,
+    0b0001_1000,
+];
+const BULB_OFF: [u8; 8] = [
+    0b0011_1100,
+    0b0100_0010,
+    0b1000_0001,
+    0b1000_0001,
+    0b0100_0010,
+    0b0011_1100,
+    0b0011_1100,
+    0b0001_1000,
+];
+const THERMOMETER: [u8; 8] = [
+    0b0001_1000,
+    0b0010_0100,
+    0b0010_0100,
+    0b0011_1100,
+    0b0011_1100,
+    0b0111_1110,
+    0b0111_1110,
+    0b0011_1100,
+];
+const DOT_ON: [u8; 8] = [
+    0b0011_1100,
+    0b0111_1110,
+    0b1111_1111,
+    0b1111_1111,
+    0b1111_1111,
+    0b1111_1111,
+    0b0111_1110,
+    0b0011_1100,
+];
+const DOT_OFF: [u8; 8] = [
+    0b0011_1100,
+    0b0100_0010,
+    0b1000_0001,
+    0b1000_0001,
+    0b1000_0001,
+    0b1000_0001,
+    0b0100_0010,
+    0b0011_1100,
+];
+
+fn draw_icon(display: &mut Display, icon: &[u8; 8], origin: Coord) {
+    let pixels = icon.iter().enumerate().flat_map(|(y, row)| {
+        (0..8).filter_map(move |x| {
+            if row & (0x80 >> x) != 0 {
+                let p = origin + Coord::new(x, y as i32);
+                Some(Pixel(p.to_unsigned(), Color::Black))
+            } else {
+                None
+            }
+        })
+    });
+    display.draw(pixels);
+}
+
+fn number(state: &State, attribute: &str) -> Option<String> {
+    state
+        .attributes
+        .get(attribute)
+        .and_then(Value::as_f64)
+        .map(|v| format!("{:.0}°", v))
+}
+
+/// Picks an icon and short text for an entity based on its domain.
+fn describe(entity: &EntityConfig, state: Option<&State>) -> (&'static [u8; 8], String) {
+    let name = entity
+        .name
+        .clone()
+        .or_else(|| {
+            state
+                .and_then(|s| s.attributes.get("friendly_name"))
+                .and_then(Value::as_str)
+                .map(str::to_string)
+        })
+        .unwrap_or_else(|| entity.id.clone());
+    let state = match state {
+        Some(state) => state,
+        None => return (&DOT_OFF, format!("{} ?", name)),
+    };
+    let on = matches!(
+        state.state.as_str(),
+        "on" | "open" | "home" | "playing" | "unlocked"
+    );
+    let domain = entity.id.split('.').next().unwrap_or_default();
+    let (icon, value) = match domain {
+        "lock" if state.state == "locked" => (&LOCKED, state.state.clone()),
+        "lock" => (&UNLOCKED, state.state.clone()),
+        "light" | "switch" | "fan" | "input_boolean" => {
+            (if on { &BULB_ON } else { &BULB_OFF }, state.state.clone())
+        }
+        "climate" => {
+            // set point first, the measured temperature in brackets
+            let value = match (
+                number(state, "temperature"),
+                number(state, "current_temperature"),
+            ) {
+                (Some(target), Some(current)) => format!("{} ({})", target, current),
+                (Some(target), None) => target,
+                _ => state.state.clone(),
+            };
+            (&THERMOMETER, value)
+        }
+        "sensor" => {
+            let unit = state
+                .attributes
+                .get("unit_of_measurement")
+                .and_then(Value::as_str)
+                .unwrap_or_default();
+            let icon = if unit.ends_with("°C") || unit.ends_with("°F") {
+                &THERMOMETER
+            } else {
+                &DOT_ON
+            };
+            (icon, format!("{}{}", state.state, unit))
+        }
+        _ => (if on { &DOT_ON } else { &DOT_OFF }, state.state.clone()),
+    };
+    (icon, format!("{} {}", name, value))
+}
+
+/// Draws the configured entities in two columns of `lines` rows.
+pub fn draw_states(
+    display: &mut Display,
+    fonts: &Fonts,
+    config: &HomeAssistantConfig,
+    states: &States,
+    origin: Coord,
+    lines: usize,
+) {
+    let line_height = fonts.body.height() as i32;
+    // two columns, each an icon then the text
+    let column = (display.clip().bottom_right[0] as i32 + 1 - origin[0]) / 2;
+    let room = (column - 10 - fonts.body.width(" ") as i32).max(0) as u32;
+    let states = states.lock().unwrap();
+    for (i, entity) in config.entities.iter().take(lines * 2).enumerate() {
+        let (icon, text) = describe(entity, states.get(&entity.id));
+        let at = origin
+            + Coord::new(
+                (i / lines) as i32 * column,
+                (i % lines) as i32 * line_height,
+            );
+        draw_icon(display, icon, at + Coord::new(0, 1));
+        let text = fonts.body.fit(&text, room);
+        fonts.body.draw(
+            display,
+            &text,
+            at + Coord::new(10, 0),
+            Color::Black,
+            Color::White,
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hyper::{
+        service::{make_service_fn, service_fn},
+        Response, Server, StatusCode,
+    };
+    use hyper_tls::HttpsConnector;
+    use std::convert::Infallible;
+    use tokio::net::{TcpListener, TcpStream};
+    use tokio_tungstenite::{accept_async, WebSocketStream};
+
+    const TOKEN: &str = "long-lived-token";
+
+    fn config(url: String) -> HomeAssistantConfig {
+        HomeAssistantConfig {
+            url,
+            token: TOKEN.to_string(),
+            websocket: false,
+            entities: vec![
+                EntityConfig {
+                    id: "lock.front_door".to_string(),
+                    name: Some("Front".to_string()),
+                },
+                EntityConfig {
+                    id: "climate.office".to_string(),
+                    name: None,
+                },
+            ],
+        }
+    }
+
+    fn states_json() -> Value {
+        json!([
+            {"entity_id": "lock.front_door", "state": "locked", "attributes": {}},
+            {"entity_id": "light.porch", "state": "on", "attributes": {}},
+            {
+                "entity_id": "climate.office",
+                "state": "heat",
+                "attributes": {
+                    "friendly_name": "Office",
+                    "temperature": 21,
+                    "current_temperature": 19.5
+                }
+            }
+        ])
+    }
+
+    #[tokio::test]
+    async fn rest_poll_keeps_configured_entities() {
+        let make_svc = make_service_fn(|_| async {
+            Ok::<_, Infallible>(service_fn(|req: Request<Body>| async move {
+                let authorized = req
+                    .headers()
+                    .get(header::AUTHORIZATION)
+                    .is_some_and(|v| v == "Bearer long-lived-token");
+                let resp = if !authorized {
+                    Response::builder()
+                        .status(StatusCode::UNAUTHORIZED)
+                        .body(Body::empty())
+                } else if req.uri().path() == "/api/states" {
+                    Response::builder().body(Body::from(states_json().to_string()))
+                } else {
+                    Response::builder()
+                        .status(StatusCode::NOT_FOUND)
+                        .body(Body::empty())
+                };
+                Ok::<_, Infallible>(resp.unwrap())
+            }))
+        });
+        let server = Server::bind(&([127, 0, 0, 1], 0).into()).serve(make_svc);
+        let addr = server.local_addr();
+        tokio::spawn(server);
+
+        let client: HttpsClient = hyper::Client::builder().build(HttpsConnector::new());
+        let config = config(format!("http://{}/", addr));
+        let states = States::default();
+        poll(&client, &config, &states).await.unwrap();
+
+        let states = states.lock().unwrap();
+        assert_eq!(states.len(), 2);
+        assert_eq!(states["lock.front_door"].state, "locked");
+        let (icon, text) = describe(&config.entities[1], states.get("climate.office"));
+        assert_eq!(icon, &THERMOMETER);
+        assert_eq!(text, "Office 21° (20°)");
+    }
+
+    async fn send(ws: &mut WebSocketStream<TcpStream>, msg: Value) {
+        ws.send(Message::Text(msg.to_string())).await.unwrap();
+    }
+
+    async fn recv(ws: &mut WebSocketStream<TcpStream>) -> Value {
+        let msg = ws.next().await.unwrap().unwrap();
+        serde_json::from_str(msg.to_text().unwrap()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn websocket_applies_initial_states_and_events() {
+        let mut listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let mut ws = accept_async(stream).await.unwrap();
+            send(&mut ws, json!({"type": "auth_required"})).await;
+            let auth = recv(&mut ws).await;
+            assert_eq!(auth["access_token"], TOKEN);
+            send(&mut ws, json!({"type": "auth_ok"})).await;
+            let get = recv(&mut ws).await;
+            assert_eq!(get["type"], "get_states");
+            let subscribe = recv(&mut ws).await;
+            assert_eq!(subscribe["event_type"], "state_changed");
+            let result = json!({"id": get["id"], "type": "result", "success": true, "result": states_json()});
+            send(&mut ws, result).await;
+            let event = json!({
+                "id": subscribe["id"],
+                "type": "event",
+                "event": {
+                    "event_type": "state_changed",
+                    "data": {
+                        "entity_id": "lock.front_door",
+                        "new_state": {"entity_id": "lock.front_door", "state": "unlocked", "attributes": {}}
+                    }
+                }
+            });
+            send(&mut ws, event).await;
+            // hold the connection open until the client has caught up
+            delay_for(Duration::from_secs(5)).await;
+        });
+
+        let config = config(format!("http://{}", addr));
+        let states = States::default();
+        subscribe(config, states.clone());
+        for _ in 0..50 {
+            if states
+                .lock()
+                .unwrap()
+                .get("lock.front_door")
+                .is_some_and(|s| s.state == "unlocked")
+            {
+                break;
+            }
+            delay_for(Duration::from_millis(20)).await;
+        }
+
+        let states = states.lock().unwrap();
+        assert_eq!(states["lock.front_door"].state, "unlocked");
+        assert_eq!(states["climate.office"].state, "heat");
+        assert!(!states.contains_key("light.porch"));
+    }
+}
@@ -0,0 +1,106 @@
+//! Weather icons, loaded from a theme directory with the compiled in set
+//! as a fallback.
+//!
+//! A theme is a directory of PNG or BMP files named after the icons below,
+//! e.g. `cloudy.png`. Each file must be 40x40 and strictly black and white;
+//! anything else is reported at startup and the built in icon used instead.
+
+use crate::{Color, Display};
+use embedded_graphics::{image::Image1BPP, prelude::*, Drawing};
+use image::{GenericImageView, Luma};
+use std::{collections::HashMap, path::Path};
+
+pub const SIZE: u32 = 40;
+
+// to convert image
+// convert src/clear.bmp -depth 1 -alpha off -fill white -resize 40x40 gray:"src/clear256.bmp"
+const EMBEDDED: [(&str, &[u8]); 12] = [
+    ("arrow_north", include_bytes!("arrow_north.bmp")),
+    ("arrow_north_east", include_bytes!("arrow_north_east.bmp")),
+    ("arrow_east", include_bytes!("arrow_east.bmp")),
+    ("arrow_south_east", include_bytes!("arrow_south_east.bmp")),
+    ("arrow_south", include_bytes!("arrow_south.bmp")),
+    ("arrow_south_west", include_bytes!("arrow_south_west.bmp")),
+    ("arrow_west", include_bytes!("arrow_west.bmp")),
+    ("arrow_north_west", include_bytes!("arrow_north_west.bmp")),
+    ("clearday", include_bytes!("clearday.bmp")),
+    ("clearnight", include_bytes!("clearnight.bmp")),
+    ("cloudy", include_bytes!("cloudy.bmp")),
+    ("partlycloudyday", include_bytes!("partlycloudyday.bmp")),
+];
+
+/// Icons packed one bit per pixel, rows first with the most significant bit
+/// leftmost and a set bit white, as `Image1BPP` expects.
+pub struct Icons {
+    icons: HashMap<&'static str, Vec<u8>>,
+}
+
+impl Default for Icons {
+    fn default() -> Self {
+        Icons {
+            icons: EMBEDDED
+                .iter()
+                .map(|&(name, data)| (name, data.to_vec()))
+                .collect(),
+        }
+    }
+}
+
+/// Reads one themed icon, insisting on the right size and only pure black
+/// and white pixels so nothing is silently thresholded.
+fn load_icon(path: &Path) -> Result<Vec<u8>, Box<dyn std::error::Error + Send + Sync>> {
+    let image = image::open(path)?;
+    if image.dimensions() != (SIZE, SIZE) {
+        return Err(format!(
+            "is {}x{}, icons must be {}x{}",
+            image.width(),
+            image.height(),
+            SIZE,
+            SIZE
+        )
+        .into());
+    }
+    let image = image.into_luma8();
+    let mut data = vec![0u8; (SIZE * SIZE / 8) as usize];
+    for (x, y, Luma([v])) in image.enumerate_pixels() {
+        let i = y * SIZE + x;
+        match v {
+            255 => data[i as usize / 8] |= 0x80 >> (i % 8),
+            0 => {}
+            _ => return Err("has gray pixels, icons must be 1-bit black and white".into()),
+        }
+    }
+    Ok(data)
+}
+
+impl Icons {
+    /// Loads the theme in `dir`, keeping the built in icon for anything the
+    /// theme lacks or gets wrong.
+    pub fn load(dir: &Path) -> Self {
+        let mut icons = Icons::default();
+        for &(name, _) in EMBEDDED.iter() {
+            let path = ["png", "bmp"]
+                .iter()
+                .map(|ext| dir.join(format!("{}.{}", name, ext)))
+                .find(|path| path.exists());
+            if let Some(path) = path {
+                match load_icon(&path) {
+                    Ok(data) => {
+                        icons.icons.insert(name, data);
+                    }
+                    Err(e) => eprintln!("icon {}: {}, using the built in one", path.display(), e),
+                }
+            }
+        }
+        icons
+    }
+
+    /// Draws the named icon with its top left corner at `origin`.
+    pub fn draw(&self, display: &mut Display, name: &str, origin: Coord) {
+        if let Some(data) = self.icons.get(name) {
+            let mut image: Image1BPP<Color> = Image1BPP::new(data, SIZE, SIZE);
+            image.translate_mut(origin);
+            display.draw(&image);
+        }
+    }
+}
@@ -0,0 +1,93 @@
+//! Time zone, language and formats used for every date and time shown.
+
+use crate::config::LocaleConfig;
+use chrono::{DateTime, FixedOffset, Local, NaiveDate, NaiveDateTime, Offset, TimeZone, Utc};
+use chrono_tz::Tz;
+use std::{convert::TryFrom, fmt::Display};
+
+pub struct Locale {
+    language: chrono::Locale,
+    /// `None` follows the host's zone.
+    timezone: Option<Tz>,
+    pub date_format: String,
+    pub time_format: String,
+}
+
+impl Locale {
+    pub fn load(config: &LocaleConfig) -> Result<Self, String> {
+        let language = chrono::Locale::try_from(config.language.as_str())
+            .map_err(|_| format!("unknown locale {:?}", config.language))?;
+        let timezone = config
+            .timezone
+            .as_ref()
+            .map(|name| name.parse::<Tz>())
+            .transpose()
+            .map_err(|e| format!("invalid timezone: {}", e))?;
+        Ok(Locale {
+            language,
+            timezone,
+            date_format: config.date_format.clone(),
+            time_format: config.time_format.clone(),
+        })
+    }
+
+    /// `time` in the configured zone, with the offset in effect at that
+    /// instant.
+    pub fn localize(&self, time: DateTime<Utc>) -> DateTime<FixedOffset> {
+        let offset = match self.timezone {
+            Some(tz) => tz.offset_from_utc_datetime(&time.naive_utc()).fix(),
+            None => Local.offset_from_utc_datetime(&time.naive_utc()).fix(),
+        };
+        time.with_timezone(&offset)
+    }
+
+    /// A wall clock time in the configured zone, the earlier one if it is
+    /// ambiguous.
+    pub fn resolve(&self, time: NaiveDateTime) -> Option<DateTime<Utc>> {
+        match self.timezone {
+            Some(tz) => tz
+                .from_local_datetime(&time)
+                .earliest()
+                .map(|t| t.with_timezone(&Utc)),
+            None => Local
+                .from_local_datetime(&time)
+                .earliest()
+                .map(|t| t.with_timezone(&Utc)),
+        }
+    }
+
+    pub fn now(&self) -> DateTime<FixedOffset> {
+        self.localize(Utc::now())
+    }
+
+    /// Formats with day and month names in the configured language.
+    pub fn format<Z>(&self, time: &DateTime<Z>, format: &str) -> String
+    where
+        Z: TimeZone,
+        Z::Offset: Display,
+    {
+        time.format_localized(format, self.language).to_string()
+    }
+
+    /// Formats a calendar date, for formats that only use date fields.
+    pub fn format_date(&self, date: NaiveDate, format: &str) -> String {
+        let noon = date.and_hms_opt(12, 0, 0).unwrap_or_default();
+        self.format(&Utc.from_utc_datetime(&noon), format)
+    }
+
+    pub fn date<Z>(&self, time: &DateTime<Z>) -> String
+    where
+        Z: TimeZone,
+        Z::Offset: Display,
+    {
+        self.format(time, &self.date_format)
+    }
+
+    pub fn time<Z>(&self, time: &DateTime<Z>) -> String
+    where
+        Z: TimeZone,
+        Z::Offset: Display,
+    {
+        self.format(time, &self.time_format)
+    }
+}
@@ -1,659 +1,788 @@
-#![feature(exclusive_range_pattern)]
+mod caldav;
+mod calendar;
+mod cli;
+mod clock;
+mod config;
+mod countdown;
+mod device;
+mod display;
+mod dither;
+mod fonts;
+mod homeassistant;
+mod icons;
+mod locale;
+mod news;
+mod quiet;
+mod sensors;
+mod server;
+mod slideshow;
+mod status;
+mod todo;
+mod transport;
+mod weather;
+mod worldclock;
 
 use byteorder::{BigEndian, WriteBytesExt};
-use bytes::buf::BufExt as _;
-use chrono::Local;
-use dotenv_codegen::dotenv;
-use embedded_graphics::{
-    drawable::Pixel,
-    image::Image1BPP,
-    prelude::{UnsignedCoord, *},
-    Drawing,
-};
-use htmlescape::decode_html;
-use hyper::client::Client;
+use calendar::Event;
+use chrono::{DateTime, FixedOffset, Timelike, Utc};
+use config::{Action, ClockConfig, Config, DisplayConfig, SlideshowConfig, Widget};
+use display::{Clip, Color, Display, Panel};
+use embedded_graphics::prelude::{UnsignedCoord, *};
+use fonts::Fonts;
+use homeassistant::States;
+use hyper::client::{Client, HttpConnector};
 use hyper_tls::HttpsConnector;
-use profont::{ProFont14Point, ProFont24Point, ProFont9Point, ProFont7Point};
+use icons::Icons;
+use locale::Locale;
 use rss::Channel;
-use serde_derive::{Deserialize, Serialize};
-use serialport::open;
-use std::{io::prelude::*, str};
-use textwrap::fill;
-
-pub const ROWS: u16 = 128;
-pub const COLS: u16 = 250;
-
-#[derive(Clone, Copy, PartialEq, Debug)]
-pub enum Color {
-    Black,
-    White,
-}
+use sensors::Readings;
+use std::{
+    collections::HashSet,
+    io::prelude::*,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+use tokio::{
+    task::{self, JoinHandle},
+    time::{delay_for, delay_until, Instant},
+};
+use transport::Transport;
+use weather::Forecast;
 
-impl PixelColor for Color {}
+pub type HttpsClient = Client<HttpsConnector<HttpConnector>>;
+
+/// How soon to try again while the panel is unplugged.
+const RECONNECT: Duration = Duration::from_secs(5);
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let matches = cli::app().get_matches();
+    let (command, sub) = matches.subcommand();
+    // the common flags may come before or after the subcommand
+    let flag = |name| {
+        sub.and_then(|m| m.value_of(name))
+            .or_else(|| matches.value_of(name))
+    };
+    if command == "list-ports" {
+        return cli::list_ports();
+    }
+    let config = Config::load(flag("config").unwrap_or(config::DEFAULT_PATH))?;
+    let screens = screens(&config, flag("display"), flag("port"))?;
 
-impl From<u8> for Color {
-    fn from(value: u8) -> Self {
-        match value {
-            0 => Color::Black,
-            1 => Color::White,
-            _ => panic!("invalid color value"),
+    match (command, sub) {
+        ("render", Some(args)) => {
+            let screen = &screens[0];
+            let dashboard = Dashboard::start(config).await?;
+            let mut buf = screen.panel.blank();
+            let mut display = Display::new(&mut buf, screen.panel);
+            dashboard.draw(screen.layout(), dashboard.assets.locale.now(), &mut display);
+            cli::write_frame(
+                &screen.panel,
+                &buf,
+                args.value_of("output"),
+                args.value_of("format"),
+            )?;
+        }
+        ("push", Some(args)) => {
+            send_each(&screens, |screen| cli::load_frame(&screen.panel, args)).await?
+        }
+        ("clear", _) => send_each(&screens, |screen| Ok(screen.panel.blank())).await?,
+        ("test-pattern", _) => {
+            let fonts = Fonts::load(&config.fonts)?;
+            send_each(&screens, |screen| {
+                let mut buf = screen.panel.blank();
+                let mut display = Display::new(&mut buf, screen.panel);
+                cli::draw_test_pattern(&mut display, &fonts);
+                Ok(buf)
+            })
+            .await?
+        }
+        ("serve", Some(args)) => {
+            let listen = args
+                .value_of("listen")
+                .unwrap_or(&config.server.listen)
+                .to_string();
+            server::serve(config, &listen).await?
         }
+        _ => run(config, screens).await?,
     }
+    Ok(())
 }
 
-impl From<u16> for Color {
-    fn from(value: u16) -> Self {
-        match value {
-            0 => Color::Black,
-            1 => Color::White,
-            _ => panic!("invalid color value"),
+/// The displays picked by `--display`, or all of them. `--port` can only
+/// stand in for one display's device.
+fn screens(
+    config: &Config,
+    name: Option<&str>,
+    port: Option<&str>,
+) -> Result<Vec<Screen>, Box<dyn std::error::Error + Send + Sync>> {
+    let displays: Vec<DisplayConfig> = config
+        .displays()
+        .into_iter()
+        .filter(|display| name.is_none_or(|name| name == display.name))
+        .collect();
+    match (displays.len(), name) {
+        (0, Some(name)) => return Err(format!("no display named {}", name).into()),
+        (2.., _) if port.is_some() => {
+            return Err("--port needs --display when several displays are configured".into())
         }
+        _ => {}
     }
+    displays
+        .into_iter()
+        .map(|display| Screen::new(config, display, port))
+        .collect()
 }
 
-struct Display<'a> {
-    buff: &'a mut [u8],
+/// Sends a frame made for each screen, reporting failures per display.
+async fn send_each<F>(
+    screens: &[Screen],
+    frame: F,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>>
+where
+    F: Fn(&Screen) -> Result<Vec<u8>, Box<dyn std::error::Error + Send + Sync>>,
+{
+    let sending: Vec<_> = screens
+        .iter()
+        .map(|screen| frame(screen).map(|frame| screen.send(frame)))
+        .collect();
+    let mut failed = 0;
+    for (screen, sending) in screens.iter().zip(sending) {
+        let sent = match sending {
+            Ok(sending) => sent(sending).await,
+            Err(e) => Err(e),
+        };
+        if let Err(e) = sent {
+            eprintln!("{}: {}", screen.name, e);
+            failed += 1;
+        }
+    }
+    match failed {
+        0 => Ok(()),
+        _ => Err(format!("{} of {} displays failed", failed, screens.len()).into()),
+    }
 }
 
-impl<'a> Display<'a> {
-    fn set_pixel(&mut self, x: u32, y: u32, color: Color) {
-        let (index, bit) = get_bit(x, y, ROWS as u32, COLS as u32);
-        let index = index as usize;
+/// Draws and sends the dashboard to every screen, then keeps each up to
+/// date on its own schedule in daemon mode.
+async fn run(
+    config: Config,
+    mut screens: Vec<Screen>,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let mut dashboard = Dashboard::start(config).await?;
+    let config = &dashboard.config;
 
-        match color {
-            Color::Black => {
-                self.buff[index] &= !bit;
+    // keep the sender around so the receiver simply never fires when
+    // there is nothing to watch
+    let (_no_changes, mut changes) = tokio::sync::mpsc::unbounded_channel();
+    if let (Some(_), Some(todo), true) = (&config.daemon, &config.todo, config.shows(Widget::Todo))
+    {
+        match todo::watch(&todo.path) {
+            Ok(watched) => changes = watched,
+            Err(e) => eprintln!("unable to watch {}: {}", todo.path.display(), e),
+        }
+    }
+    let fetch_every =
+        Duration::from_secs(config.daemon.as_ref().map_or(0, |d| d.fetch_minutes * 60));
+    let mut next_fetch = Instant::now() + fetch_every;
+
+    // every board's presses, tagged with the screen they came from
+    let (pressed, mut presses) = tokio::sync::mpsc::unbounded_channel();
+    for (i, screen) in screens.iter_mut().enumerate() {
+        if let Some(mut buttons) = screen.transport.lock().unwrap().buttons() {
+            let pressed = pressed.clone();
+            tokio::spawn(async move {
+                while let Some(button) = buttons.recv().await {
+                    if pressed.send((i, button)).is_err() {
+                        break;
+                    }
+                }
+            });
+        }
+    }
+
+    loop {
+        // draw every screen that is due, then send to them all at once
+        let mut sending = Vec::new();
+        for (i, screen) in screens.iter().enumerate() {
+            if screen.due > Instant::now() {
+                continue;
             }
-            Color::White => {
-                self.buff[index] |= bit;
+            let now = dashboard.assets.locale.now();
+            let mut buf = screen.panel.blank();
+            let mut display = Display::new(&mut buf, screen.panel);
+            let slideshow = dashboard.draw(screen.layout(), now, &mut display);
+            let status = screen.transport.lock().unwrap().status();
+            if let (true, Some(volts)) = (screen.battery, status.and_then(|s| s.battery)) {
+                // the last few rows are left free by every layout
+                let bottom = screen.panel.size().1 as i32 - 8;
+                status::draw_battery(&mut display, volts, Coord::new(0, bottom));
             }
+            sending.push((i, slideshow, screen.send(buf)));
+        }
+
+        let mut failed = 0;
+        for (i, slideshow, sending) in sending {
+            let screen = &mut screens[i];
+            let sent = sent(sending).await;
+            let config = &dashboard.config;
+            let daemon = match &config.daemon {
+                Some(daemon) => daemon,
+                None => {
+                    if let Err(e) = sent {
+                        eprintln!("{}: {}", screen.name, e);
+                        failed += 1;
+                    }
+                    continue;
+                }
+            };
+            // redraw on the minute so the clock stays right
+            let now = dashboard.assets.locale.now();
+            let refresh = screen.refresh_minutes.unwrap_or(daemon.refresh_minutes);
+            let mut wait = Duration::from_secs(refresh.max(1) * 60 - u64::from(now.second()));
+            if let Some(slideshow) = slideshow.filter(|s| !s.clock) {
+                wait = Duration::from_secs(slideshow::until_next(slideshow, now));
+            } else if slideshow.is_none() && screen.layout().clock.seconds {
+                // ticking seconds need a panel that can partially refresh
+                wait =
+                    Duration::from_millis(1000 - u64::from(now.timestamp_subsec_millis() % 1000));
+            }
+            // nothing is redrawn until the quiet period ends
+            match quiet::current(&config.quiet, now) {
+                Some(period) => wait = Duration::from_secs(quiet::until_end(period, now)),
+                None => {
+                    if let Some(start) = quiet::until_start(&config.quiet, now) {
+                        wait = wait.min(Duration::from_secs(start));
+                    }
+                }
+            }
+            match sent {
+                Ok(()) if screen.failing => {
+                    eprintln!("{}: sending again", screen.name);
+                    screen.failing = false;
+                }
+                Ok(()) => {}
+                Err(e) => {
+                    eprintln!(
+                        "{}: {}, trying again in {}s",
+                        screen.name,
+                        e,
+                        RECONNECT.as_secs()
+                    );
+                    screen.failing = true;
+                    wait = wait.min(RECONNECT);
+                }
+            }
+            screen.due = Instant::now() + wait;
+        }
+        if dashboard.config.daemon.is_none() {
+            return match failed {
+                0 => Ok(()),
+                _ => Err(format!("{} of {} displays failed", failed, screens.len()).into()),
+            };
+        }
+
+        let due = screens
+            .iter()
+            .map(|s| s.due)
+            .min()
+            .unwrap_or_else(Instant::now);
+        tokio::select! {
+            _ = delay_until(due) => {}
+            Some(()) = changes.recv() => screens.iter_mut().for_each(|s| s.due = Instant::now()),
+            Some((i, button)) = presses.recv() => {
+                let action = match dashboard.config.action(button) {
+                    Some(action) => action,
+                    None => continue,
+                };
+                eprintln!("{}: button {}, {:?}", screens[i].name, button, action);
+                match action {
+                    Action::NextPage => screens[i].page = (screens[i].page + 1) % screens[i].pages.len(),
+                    Action::Refresh => {
+                        next_fetch = Instant::now() + fetch_every;
+                        dashboard.refresh().await;
+                        screens.iter_mut().for_each(|s| s.due = Instant::now());
+                    }
+                    Action::ToggleNews => {
+                        let page = screens[i].page;
+                        let layout = &mut screens[i].pages[page];
+                        layout.toggle_news(&dashboard.config);
+                        // news is only fetched once something shows it
+                        if layout.bottom == Widget::News && !dashboard.news {
+                            dashboard.news = true;
+                            dashboard.refresh().await;
+                        }
+                    }
+                    Action::Acknowledge => {
+                        dashboard.acknowledge(Utc::now());
+                        screens.iter_mut().for_each(|s| s.due = Instant::now());
+                    }
+                }
+                screens[i].due = Instant::now();
+            }
+        }
+        if next_fetch <= Instant::now() {
+            next_fetch = Instant::now() + fetch_every;
+            dashboard.refresh().await;
         }
     }
 }
 
-impl<'a> Drawing<Color> for Display<'a> {
-    fn draw<T>(&mut self, item_pixels: T)
-    where
-        T: IntoIterator<Item = Pixel<Color>>,
-    {
-        for Pixel(UnsignedCoord(x, y), colour) in item_pixels {
-            self.set_pixel(x, y, colour);
+/// One panel being driven, with its own layout and schedule.
+struct Screen {
+    name: String,
+    panel: Panel,
+    /// Shared with the thread sending to it.
+    transport: Arc<Mutex<Box<dyn Transport>>>,
+    pages: Vec<Layout>,
+    /// Which of `pages` is showing.
+    page: usize,
+    /// Whether to draw the reported battery level.
+    battery: bool,
+    refresh_minutes: Option<u64>,
+    /// When it next needs redrawing.
+    due: Instant,
+    /// Whether the last send failed, to report when it comes back.
+    failing: bool,
+}
+
+impl Screen {
+    fn new(
+        config: &Config,
+        display: DisplayConfig,
+        port: Option<&str>,
+    ) -> Result<Self, Box<dyn std::error::Error + Send + Sync>> {
+        let pages = Layout::pages(config, &display);
+        let name = display.name;
+        let context = |e: String| format!("{}: {}", name, e);
+        let panel = display.panel.panel().map_err(context)?;
+        let transport =
+            transport::open(port, &display.device).map_err(|e| context(e.to_string()))?;
+        Ok(Screen {
+            panel,
+            transport: Arc::new(Mutex::new(transport)),
+            pages,
+            page: 0,
+            battery: display.device.battery,
+            refresh_minutes: display.refresh_minutes,
+            due: Instant::now(),
+            failing: false,
+            name,
+        })
+    }
+
+    fn layout(&self) -> &Layout {
+        &self.pages[self.page]
+    }
+
+    /// Sends `frame` on a thread of its own, so a slow or unreachable board
+    /// holds up neither the executor nor the other screens.
+    fn send(
+        &self,
+        frame: Vec<u8>,
+    ) -> JoinHandle<Result<(), Box<dyn std::error::Error + Send + Sync>>> {
+        let transport = self.transport.clone();
+        task::spawn_blocking(move || transport.lock().unwrap().send(&frame))
+    }
+}
+
+/// What differs between displays showing the same data.
+struct Layout {
+    bottom: Widget,
+    clock: ClockConfig,
+    /// What news was toggled on over, to go back to.
+    before_news: Option<Widget>,
+}
+
+impl Layout {
+    /// The display's first page.
+    fn new(config: &Config, display: &DisplayConfig) -> Self {
+        Layout::pages(config, display).remove(0)
+    }
+
+    /// Each configured page over the display's own layout, or just that
+    /// when there are no pages.
+    fn pages(config: &Config, display: &DisplayConfig) -> Vec<Self> {
+        let base = Layout {
+            bottom: display.bottom.unwrap_or_else(|| config.bottom()),
+            clock: display
+                .clock
+                .clone()
+                .unwrap_or_else(|| config.clock.clone()),
+            before_news: None,
+        };
+        if config.pages.is_empty() {
+            return vec![base];
+        }
+        config
+            .pages
+            .iter()
+            .map(|page| Layout {
+                bottom: page.bottom.unwrap_or(base.bottom),
+                clock: page.clock.clone().unwrap_or_else(|| base.clock.clone()),
+                before_news: None,
+            })
+            .collect()
+    }
+
+    /// Shows news, or goes back to what it replaced. A page set up to show
+    /// news switches to the first other widget configured.
+    fn toggle_news(&mut self, config: &Config) {
+        if self.bottom != Widget::News {
+            self.before_news = Some(self.bottom);
+            self.bottom = Widget::News;
+            return;
+        }
+        let configured = [
+            (Widget::Calendar, config.calendar.is_some()),
+            (Widget::Todo, config.todo.is_some()),
+            (Widget::HomeAssistant, config.homeassistant.is_some()),
+            (Widget::Countdown, config.countdown.is_some()),
+            (Widget::WorldClock, config.worldclock.is_some()),
+            (Widget::Image, config.image.is_some()),
+        ];
+        let fallback = configured.iter().find(|(_, set)| *set).map(|&(w, _)| w);
+        if let Some(widget) = self.before_news.take().or(fallback) {
+            self.bottom = widget;
         }
     }
 }
 
-#[tokio::main]
-async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
-    let mut serialport = open("/dev/ttyACM2").expect("unable to open serial port");
-
-    let token = dotenv!("API_KEY");
-    let lat: f32 = 31.1171;
-    let long: f32 = -97.7278;
-
-    let https = HttpsConnector::new();
-    let client = Client::builder().build::<_, hyper::Body>(https);
-    let weather_uri = format!(
-        "https://api.darksky.net/forecast/{}/{},{}",
-        token, lat, long
-    )
-    .parse()?;
-    let news_uri = "https://news.yahoo.com/rss/home".parse()?;
-    let (weather_resp, news_resp) =
-        tokio::try_join!(client.get(weather_uri), client.get(news_uri))?;
-
-    let (weather_body, news_body) = tokio::try_join!(
-        hyper::body::aggregate(weather_resp),
-        hyper::body::aggregate(news_resp)
-    )?;
-
-    // try to parse as json with serde_json
-    let forecast: Forecast = serde_json::from_reader(weather_body.reader())?;
-    let news = Channel::read_from(news_body.reader()).unwrap();
-
-    let mut buf = [255u8; ROWS as usize * COLS as usize / 8];
-    let mut display = Display { buff: &mut buf };
-    let now = Local::now();
-
-    let formatted = now.format("%H:%M").to_string();
-    let t = ProFont24Point::render_str(&formatted)
-        .stroke(Some(Color::Black))
-        .fill(Some(Color::White))
-        .translate(Coord::new(0, 20));
-    display.draw(t);
-
-    let formatted = now.format("%d/%m/%y").to_string();
-    let t = ProFont14Point::render_str(&formatted)
-        .stroke(Some(Color::Black))
-        .fill(Some(Color::White))
-        .translate(Coord::new(0, 0));
-    display.draw(t);
-
-    let mut count = 0;
-    for titles in news.items().iter().take(2) {
-        let decoded = decode_html(titles.title().unwrap()).unwrap();
-        let text = fill(&decoded, 41);
-        for line in text.split('\n') {
-            count += 1;
-            if count < 4 {
-                let t = ProFont9Point::render_str(&line)
-                    .stroke(Some(Color::Black))
-                    .fill(Some(Color::White))
-                    .translate(Coord::new(0, 76 + (count * 11)));
-                display.draw(t);
-            }
+/// The config and everything loaded, fetched or subscribed to for it.
+struct Dashboard {
+    config: Config,
+    assets: Assets,
+    client: HttpsClient,
+    live: Live,
+    remote: Remote,
+    /// Whether news is fetched, which a button can turn on.
+    news: bool,
+    /// Events whose alert was dismissed, by summary and start, kept across
+    /// fetches.
+    acknowledged: HashSet<(String, DateTime<Utc>)>,
+}
+
+impl Dashboard {
+    /// Loads the assets, starts the subscriptions and does a first fetch.
+    async fn start(config: Config) -> Result<Self, Box<dyn std::error::Error + Send + Sync>> {
+        let assets = Assets {
+            icons: config
+                .icons
+                .as_deref()
+                .map_or_else(Icons::default, Icons::load),
+            fonts: Fonts::load(&config.fonts)?,
+            locale: Locale::load(&config.locale)?,
+        };
+
+        let https = HttpsConnector::new();
+        let client: HttpsClient = Client::builder().build::<_, hyper::Body>(https);
+        let live = Live {
+            readings: config.mqtt.clone().map(sensors::subscribe),
+            states: config.homeassistant.as_ref().map(|ha| {
+                let states = States::default();
+                if ha.websocket {
+                    homeassistant::subscribe(ha.clone(), states.clone());
+                }
+                states
+            }),
+        };
+        let news = config.shows(Widget::News);
+        let remote = fetch(&client, &config, &assets.locale, &live, news).await?;
+        if live.readings.is_some() || config.homeassistant.as_ref().is_some_and(|ha| ha.websocket) {
+            // give the subscriptions a moment to deliver their initial state
+            delay_for(Duration::from_secs(2)).await;
         }
+        Ok(Dashboard {
+            config,
+            assets,
+            client,
+            live,
+            remote,
+            news,
+            acknowledged: HashSet::new(),
+        })
     }
 
-    if let Some(currently) = forecast.currently {
-        if let Some(temp) = currently.temperature {
-            let temp = format!("{:2.0}°", temp);
-            let t = ProFont14Point::render_str(&temp)
-                .stroke(Some(Color::Black))
-                .fill(Some(Color::White))
-                .translate(Coord::new(130, 0));
-            display.draw(t);
+    /// Fetches again, keeping the last copy if that fails.
+    async fn refresh(&mut self) {
+        let fetched = fetch(
+            &self.client,
+            &self.config,
+            &self.assets.locale,
+            &self.live,
+            self.news,
+        );
+        match fetched.await {
+            Ok(fetched) => self.remote = fetched,
+            Err(e) => eprintln!("unable to refresh data, keeping the last copy: {}", e),
         }
-        if let Some(precip) = currently.precip_probability {
-            let precip = format!("{:2.0}%", precip);
-            let t = ProFont14Point::render_str(&precip)
-                .stroke(Some(Color::Black))
-                .fill(Some(Color::White))
-                .translate(Coord::new(162, 0));
-            display.draw(t);
+        let acknowledged = &self.acknowledged;
+        for event in self.remote.events.iter_mut().flatten() {
+            event.acknowledged = acknowledged.contains(&(event.summary.clone(), event.start));
         }
-        if let Some(wind) = currently.wind_speed {
-            if let Some(dir) = currently.wind_bearing {
-                // to convert image
-                // convert src/clear.bmp -depth 1 -alpha off -fill white -resize 40x40 gray:"src/clear256.bmp"
-                let mut image = match dir as i32 {
-                    337..360 | 0..22 => Image1BPP::new(include_bytes!("arrow_north.bmp"), 40, 40), //north
-                    22..67 => Image1BPP::new(include_bytes!("arrow_north_east.bmp"), 40, 40), //north_east
-                    67..112 => Image1BPP::new(include_bytes!("arrow_east.bmp"), 40, 40),      //east
-                    112..157 => Image1BPP::new(include_bytes!("arrow_south_east.bmp"), 40, 40), //south_east
-                    157..202 => Image1BPP::new(include_bytes!("arrow_south.bmp"), 40, 40), //south
-                    202..247 => Image1BPP::new(include_bytes!("arrow_south_west.bmp"), 40, 40), //south_west
-                    247..292 => Image1BPP::new(include_bytes!("arrow_west.bmp"), 40, 40), //west
-                    292..337 => Image1BPP::new(include_bytes!("arrow_north_west.bmp"), 40, 40), //northwest
-                    _ => Image1BPP::new(include_bytes!("arrow_north.bmp"), 40, 40),
-                };
+    }
 
-                image.translate_mut(Coord::new(86, 44));
-                display.draw(&image);
+    /// Stops highlighting the events alerting at `now`.
+    fn acknowledge(&mut self, now: DateTime<Utc>) {
+        for event in self.remote.events.iter_mut().flatten() {
+            if calendar::alerting(event, now) {
+                event.acknowledged = true;
+                self.acknowledged
+                    .insert((event.summary.clone(), event.start));
             }
-            let wind = format!("{:2.0}MPH", wind);
-            let t = ProFont14Point::render_str(&wind)
-                .stroke(Some(Color::Black))
-                .fill(Some(Color::White))
-                .translate(Coord::new(200, 0));
-            display.draw(t);
         }
-        let mut count = 0;
-        if let Some(summary) = currently.summary {
-            let summary = format!("Currently: {}", summary);
-            let text = fill(&summary, 20);
-            for (i, line) in text.split('\n').enumerate() {
-                count += i as i32;
-                let t = ProFont9Point::render_str(&line)
-                    .stroke(Some(Color::Black))
-                    .fill(Some(Color::White))
-                    .translate(Coord::new(130, 20 + (count * 10)));
-                display.draw(t);
-            }
+    }
+
+    /// Draws the slideshow if it is scheduled, returning it, or the
+    /// dashboard otherwise.
+    fn draw(
+        &self,
+        layout: &Layout,
+        now: DateTime<FixedOffset>,
+        display: &mut Display,
+    ) -> Option<&SlideshowConfig> {
+        if let Some(period) = quiet::current(&self.config.quiet, now) {
+            let wake = now + chrono::Duration::seconds(quiet::until_end(period, now) as i64);
+            let assets = &self.assets;
+            quiet::draw_sleep(
+                display,
+                &assets.fonts,
+                &assets.locale,
+                &assets.icons,
+                &self.remote.forecast,
+                wake,
+            );
+            return None;
         }
-        if let Some(daily) = forecast.daily {
-            if let Some(data) = daily.data {
-                if let Some(summary) = &data[0].summary {
-                    let summary = format!("Today: {}", summary);
-                    let text = fill(&summary, 20);
-                    for line in text.split('\n') {
-                        count += 1;
-                        let t = ProFont9Point::render_str(&line)
-                            .stroke(Some(Color::Black))
-                            .fill(Some(Color::White))
-                            .translate(Coord::new(130, 20 + (count * 10)));
-                        display.draw(t);
-                    }
-                }
-                if let Some(summary) = &data[1].summary {
-                    let summary = format!("Tomorrow: {}", summary);
-                    let text = fill(&summary, 20);
-                    for line in text.split('\n') {
-                        count += 1;
-                        let t = ProFont9Point::render_str(&line)
-                            .stroke(Some(Color::Black))
-                            .fill(Some(Color::White))
-                            .translate(Coord::new(130, 20 + (count * 10)));
-                        display.draw(t);
-                    }
+        let slideshow = match &self.config.slideshow {
+            Some(slideshow) if slideshow.active(now.time()) => Some(slideshow),
+            _ => None,
+        };
+        let dashboard = |display: &mut Display| {
+            render(
+                &self.config,
+                layout,
+                &self.remote,
+                &self.live,
+                &self.assets,
+                now,
+                display,
+            )
+        };
+        match slideshow {
+            Some(slideshow) => {
+                if let Err(e) = slideshow::draw_slide(
+                    display,
+                    &self.assets.fonts,
+                    &self.assets.locale,
+                    slideshow,
+                    now,
+                ) {
+                    eprintln!("slideshow: {}", e);
+                    dashboard(display);
                 }
             }
+            None => dashboard(display),
         }
-        match currently.icon {
-            Some(Icon::ClearDay) => {
-                let mut image = Image1BPP::new(include_bytes!("clearday.bmp"), 40, 40);
-                image.translate_mut(Coord::new(86, 0));
-                display.draw(&image);
-            }
-            Some(Icon::ClearNight) => {
-                let mut image = Image1BPP::new(include_bytes!("clearnight.bmp"), 40, 40);
-                image.translate_mut(Coord::new(86, 0));
-                display.draw(&image);
-            }
-            Some(Icon::Cloudy) => {
-                let mut image = Image1BPP::new(include_bytes!("cloudy.bmp"), 40, 40);
-                image.translate_mut(Coord::new(86, 0));
-                display.draw(&image);
-            }
-            Some(Icon::PartlyCloudyDay) => {
-                let mut image = Image1BPP::new(include_bytes!("partlycloudyday.bmp"), 40, 40);
-                image.translate_mut(Coord::new(86, 0));
-                display.draw(&image);
-            }
-            _ => {}
-        }
+        slideshow
     }
-
-    let mut buff = Vec::new();
-    buff.write_u32::<BigEndian>(display.buff.len() as u32)
-        .unwrap();
-    serialport.write(&buff).unwrap();
-    serialport.write(display.buff).unwrap();
-    Ok(())
 }
 
-fn get_bit(x: u32, y: u32, width: u32, height: u32) -> (u32, u8) {
-    (y / 8 + (height - 1 - x) * (width / 8), 0x80 >> (y % 8))
+/// Everything fetched over the network, refreshed every `fetch_minutes` in
+/// daemon mode.
+struct Remote {
+    forecast: Forecast,
+    news: Option<Channel>,
+    events: Option<Vec<Event>>,
 }
 
-#[derive(Deserialize, Debug)]
-struct Forecast {
-    latitude: f32,
-    longitude: f32,
-    timezone: String,
-    currently: Option<Datapoint>,
-    pub daily: Option<Datablock>,
-    pub hourly: Option<Datablock>,
-    pub minutely: Option<Datablock>,
-    pub flags: Option<Flags>,
+/// Data pushed by background subscriptions, or polled into place by
+/// [`fetch`] for sources without one.
+struct Live {
+    readings: Option<Readings>,
+    states: Option<States>,
 }
 
-/// A datapoint within a [`Datablock`], where there is usually multiple.
-///
-/// All fields are optional _except for [`time`]_, as some data may not be
-/// available for a location at a given point in time.
-///
-/// All of the data oriented fields may have associated `error` fields,
-/// representing the confidence in a prediction or value. An example is
-/// [`precip_accumulation`], which has an associated error field of
-/// [`precip_accumulation_error`]. Those fields represent standard deviations of
-/// the value of the associated field. Smaller error values represent greater
-/// confidence levels, while larger error values represent less confidence.
-/// These fields are omitted where the confidence is not precisely known.
-///
-/// [`Datablock`]: struct.Datablock.html
-/// [`time`]: #structfield.time
-#[derive(Clone, Debug, Deserialize, Serialize)]
-#[serde(rename_all = "camelCase")]
-pub struct Datapoint {
-    /// The unix timestamp representing when the daytime high apparent
-    /// temperature occurs.
-    ///
-    /// **Note**: This is only present on the `daily` block.
-    pub apparent_temperature_max_time: Option<u64>,
-    /// The daytime high apparent temperature.
-    ///
-    /// **Note**: This is only present on the `daily` block.
-    pub apparent_temperature_max: Option<f64>,
-    /// The unix timestamp representing when the overnight low apparent
-    /// temperature occurs.
-    ///
-    /// **Note**: This is only present on the `daily` block.
-    pub apparent_temperature_min_time: Option<u64>,
-    /// The overnight low apparent temperature.
-    ///
-    /// **Note**: This is only present on the `daily` block.
-    pub apparent_temperature_min: Option<f64>,
-    /// The apparent (or "feels like") temperature in degrees Fahrenheit.
-    ///
-    /// **Note**: This is not present on `daily`.
-    pub apparent_temperature: Option<f64>,
-    /// The amount of error possible within the [`cloud_cover`] value.
-    ///
-    /// [`cloud_cover`]: #structfield.cloud_cover
-    pub cloud_cover_error: Option<f64>,
-    /// The percentage of sky occluded by clouds.
-    ///
-    /// This value is between `0` and `1`, inclusively.
-    pub cloud_cover: Option<f64>,
-    /// The amount of error possible within the [`dew_point`] value.
-    ///
-    /// [`dew_point`]: #structfield.dew_point
-    pub dew_point_error: Option<f64>,
-    /// The dew point in degrees Fahrenheit.
-    pub dew_point: Option<f64>,
-    /// The amount of error possible within the [`humidity`] value.
-    ///
-    /// [`humidity`]: #structfield.humidity
-    pub humidity_error: Option<f64>,
-    /// The relative humidity.
-    ///
-    /// This value is between `0` and `1`, inclusively.
-    pub humidity: Option<f64>,
-    /// A machine-readable summary of the datapoint, suitable for selecting an
-    /// icon to display.
-    pub icon: Option<Icon>,
-    /// The fractional part of the [lunation number] during the given day.
-    ///
-    /// A value of `0` corresponds to a new moon, `0.25` to a first quarter
-    /// moon, `0.5` to a full moon, `0.75` to a last quarter moon.
-    ///
-    /// **Note**: This is only present on the `daily` block.
-    pub moon_phase: Option<f64>,
-    /// The approximate direction of the nearest storm in degrees, with true
-    /// north at 0 degrees and progressing clockwise.
-    ///
-    /// If `nearestStormDistance` is `0`, then this value will not be present.
-    ///
-    /// **Note**: This is only present on the `currently` block.
-    pub nearest_storm_bearing: Option<f64>,
-    /// The approximate distance to the nearest storm in miles.
-    ///
-    /// A storm distance of `0` doesn't necessarily refer to a storm at the
-    /// requested location, but rather a storm in the vicinity of that location.
-    ///
-    /// **Note**: This is only present on the `currently` block.
-    pub nearest_storm_distance: Option<f64>,
-    /// The amount of error possible within the [`ozone`] value.
-    ///
-    /// [`ozone`]: #structfield.ozone
-    pub ozone_error: Option<f64>,
-    /// The columnar density of total atmospheric ozone at the given time in
-    /// Dobson units.
-    pub ozone: Option<f64>,
-    /// The amount of error possible within the [`precip_accumulation`] value.
-    ///
-    /// [`precip_accumulation`]: #structfield.precip_accumulation
-    pub precip_accumulation_error: Option<f64>,
-    /// The amount of snowfall accumulation expected to occur, in inches.
-    ///
-    /// If no snowfall is expected, this will be None.
-    ///
-    /// **Note**: This is only present on `hourly` and `daily` blocks.
-    pub precip_accumulation: Option<f64>,
-    /// The amount of error possible within the [`precip_intensity`] value.
-    ///
-    /// [`precip_intensity`]: #structfield.precip_intensity
-    pub precip_intensity_error: Option<f64>,
-    /// The amount of error possible within the [`precip_intensity_max`] value.
-    ///
-    /// [`precip_intensity_max`]: #structfield.precip_intensity_max
-    pub precip_intensity_max_error: Option<f64>,
-    /// The unix timestamp of when [`precip_intensity_max`] occurs during a
-    /// given day.
-    ///
-    /// **Note**: This is only present on the `daily` block.
-    ///
-    /// [`precip_intensity_max`]: #structfield.precip_intensity_max
-    pub precip_intensity_max_time: Option<u64>,
-    /// The maximum value of [`precip_intensity`] during a given day.
-    ///
-    /// **Note**: This is only present on the `daily` block.
-    ///
-    /// [`precip_intensity`]: #structfield.precip_intensity
-    pub precip_intensity_max: Option<f64>,
-    /// The intensity (in inches of liquid water per hour) precipitation
-    /// occurring at the given time.
-    ///
-    /// This value is conditional on probability (that is, assuming any
-    /// precipitation occurs at all) for `minutely` datapoints, and
-    /// unconditional otherwise.
-    pub precip_intensity: Option<f64>,
-    /// The amount of error possible within the [`precip_probability`] value.
-    ///
-    /// [`precip_probability`]: #structfield.precip_probability
-    pub precip_probability_error: Option<f64>,
-    /// The probably of precipitation occurring.
-    ///
-    /// This value is between `0` and `1`, inclusively.
-    pub precip_probability: Option<f64>,
-    /// The type of precipitation occurring at a given time.
-    ///
-    /// If [`precip_intensity`] is `0`, then this field will be `None`.
-    ///
-    /// Additionally, due to the lack of data in DarkSky sources, historical
-    /// `precip_type` values is usually estimated, rather than observed.
-    ///
-    /// [`precip_intensity`]: #structfield.precip_intensity
-    pub precip_type: Option<PrecipitationType>,
-    /// The amount of error possible within the [`pressure`] value.
-    ///
-    /// [`pressure`]: #structfield.pressure
-    pub pressure_error: Option<f64>,
-    /// The sea-level air pressure in millibars.
-    pub pressure: Option<f64>,
-    /// A human-readable text summary of the datapoint.
-    ///
-    /// **Note**: Do not use this for automated icon display purposes, use the
-    /// [`icon`] field instead.
-    ///
-    /// [`icon`]: #structfield.icon
-    pub summary: Option<String>,
-    /// The unix timestamp of when the sun will rise during a given day.
-    ///
-    /// **Note**: This is only present on the `daily` block.
-    pub sunrise_time: Option<u64>,
-    /// The unix timestamp of when the sun will set during a given day.
-    ///
-    /// **Note**: This is only present on the `daily` block.
-    pub sunset_time: Option<u64>,
-    /// The overnight low temperature.
-    ///
-    /// **Note**: This is only present on the `daily` block.
-    pub temperature_low: Option<f64>,
-    /// The unix timestamp representing when the overnight low temperature
-    /// occurs.
-    ///
-    /// **Note**: This is only present on the `daily` block.
-    pub temperature_low_time: Option<u64>,
-    /// The daytime high temperature.
-    ///
-    /// **Note**: This is only present on the `daily` block.
-    pub temperature_high: Option<f64>,
-    /// The unix timestamp representing when the daytime high temperature
-    /// occurs.
-    ///
-    /// **Note**: This is only present on the `daily` block.
-    pub temperature_high_time: Option<u64>,
-    /// The amount of error possible within the [`temperature_max`] value.
-    ///
-    /// [`temperature_max`]: #structfield.temperature_max
-    pub temperature_max_error: Option<f64>,
-    /// The unix timestamp representing when the maximum temperature during a
-    /// given date occurs.
-    ///
-    /// **Note**: This is only present on the `daily` block.
-    pub temperature_max_time: Option<u64>,
-    /// The maximum temperature during a given date.
-    ///
-    /// **Note**: This is only present on the `daily` block.
-    pub temperature_max: Option<f64>,
-    /// The amount of error possible within the [`temperature_min`] value.
-    ///
-    /// [`temperature_min`]: #structfield.temperature_min
-    pub temperature_min_error: Option<f64>,
-    /// The unix timestamp representing when the minimum temperature during a
-    /// given date occurs.
-    ///
-    /// **Note**: This is only present on the `daily` block.
-    pub temperature_min_time: Option<u64>,
-    /// The minimum temperature during a given date.
-    ///
-    /// **Note**: This is only present on the `daily` block.
-    pub temperature_min: Option<f64>,
-    /// The amount of error possible within the [`temperature`] value.
-    ///
-    /// [`temperature`]: #structfield.temperature
-    pub temperature_error: Option<f64>,
-    /// The air temperature in degrees Fahrenheit.
-    pub temperature: Option<f64>,
-    /// The unix timestamp at which the datapoint begins.
-    ///
-    /// `minutely` datapoints are always aligned to the top of the minute.
-    ///
-    /// `hourly` datapoints align to the top of the hour.
-    ///
-    /// `daily` datapoints align to midnight of the day.
-    ///
-    /// All are according to the local timezone.
-    pub time: u64,
-    /// The UV index.
-    pub uv_index: Option<u64>,
-    /// The unix timestamp of when the maximum [`uv_index`] occurs during the
-    /// given day.
-    ///
-    /// [`uv_index`]: #structfield.uv_index
-    pub uv_index_time: Option<u64>,
-    /// The amount of error possible within the [`visibility`] value.
-    ///
-    /// [`visibility`]: #structfield.visibility
-    pub visibility_error: Option<f64>,
-    /// The average visibility in miles, capped at 10 miles.
-    pub visibility: Option<f64>,
-    /// The amount of error possible within the [`wind_bearing`] value.
-    ///
-    /// [`wind_bearing`]: #structfield.wind_bearing
-    pub wind_bearing_error: Option<f64>,
-    /// The direction that the wind is coming from in degrees.
-    ///
-    /// True north is at 0 degrees, progressing clockwise.
-    ///
-    /// If [`wind_speed`] is `0`, then this value will not be defined.
-    ///
-    /// [`wind_speed`]: #structfield.wind_speed
-    pub wind_bearing: Option<f64>,
-    /// The wind gust speed in miles per hour.
-    pub wind_gust: Option<f64>,
-    /// The amount of time that the wind gust is expected to occur.
-    pub wind_gust_time: Option<u64>,
-    /// The amount of error possible within the [`wind_speed`] value.
-    ///
-    /// [`wind_speed`]: #structfield.wind_speed
-    pub wind_speed_error: Option<f64>,
-    /// The wind speed in miles per hour.
-    pub wind_speed: Option<f64>,
+/// Icons, fonts and locale settings, loaded once at startup.
+struct Assets {
+    icons: Icons,
+    fonts: Fonts,
+    locale: Locale,
 }
 
-/// The type of precipitation that is happening within a [`Datapoint`].
-///
-/// [`Datapoint`]: struct.Datapoint.html
-#[derive(Copy, Clone, Debug, Deserialize, Eq, Hash, PartialEq, PartialOrd, Ord, Serialize)]
-pub enum PrecipitationType {
-    /// Indicator that the type of precipitation is rain.
-    #[serde(rename = "rain")]
-    Rain,
-    /// Indicator that the type of precipitation is sleet.
-    #[serde(rename = "sleet")]
-    Sleet,
-    /// Indicator that the type of precipitation is snow.
-    #[serde(rename = "snow")]
-    Snow,
+async fn fetch(
+    client: &HttpsClient,
+    config: &Config,
+    locale: &Locale,
+    live: &Live,
+    news: bool,
+) -> Result<Remote, Box<dyn std::error::Error + Send + Sync>> {
+    let (forecast, news) = tokio::try_join!(weather::fetch(client), async {
+        match news {
+            true => news::fetch(client).await.map(Some),
+            false => Ok(None),
+        }
+    })?;
+    let events = match &config.calendar {
+        Some(calendar) => Some(calendar::load(client, calendar, locale, Utc::now()).await),
+        None => None,
+    };
+    if let (Some(ha), Some(states)) = (&config.homeassistant, &live.states) {
+        if !ha.websocket {
+            if let Err(e) = homeassistant::poll(client, ha, states).await {
+                eprintln!("unable to poll home assistant {}: {}", ha.url, e);
+            }
+        }
+    }
+    Ok(Remote {
+        forecast,
+        news,
+        events,
+    })
 }
 
-/// A safe representation of the indicated weather. This is useful for matching
-/// and presenting an emoji or other weather symbol or representation.
-#[derive(Copy, Clone, Debug, Deserialize, Eq, Hash, PartialEq, PartialOrd, Ord, Serialize)]
-pub enum Icon {
-    /// The day's sky is clear.
-    #[serde(rename = "clear-day")]
-    ClearDay,
-    /// The night sky is clear.
-    #[serde(rename = "clear-night")]
-    ClearNight,
-    /// The sky is cloudy.
-    #[serde(rename = "cloudy")]
-    Cloudy,
-    /// It is foggy.
-    #[serde(rename = "fog")]
-    Fog,
-    /// Not actively in use
-    #[serde(rename = "hail")]
-    Hail,
-    /// The day's sky is partly cloudy.
-    #[serde(rename = "partly-cloudy-day")]
-    PartlyCloudyDay,
-    /// The night's sky is partly night.
-    #[serde(rename = "partly-cloudy-night")]
-    PartlyCloudyNight,
-    /// The weather is rain.
-    #[serde(rename = "rain")]
-    Rain,
-    /// The weather is sleet.
-    #[serde(rename = "sleet")]
-    Sleet,
-    /// The weather is snow.
-    #[serde(rename = "snow")]
-    Snow,
-    /// Not actively in use
-    #[serde(rename = "thunderstorm")]
-    Thunderstorm,
-    /// Not actively in use
-    #[serde(rename = "tornado")]
-    Tornado,
-    /// The weather is windy.
-    #[serde(rename = "wind")]
-    Wind,
+fn render(
+    config: &Config,
+    layout: &Layout,
+    remote: &Remote,
+    live: &Live,
+    assets: &Assets,
+    now: DateTime<FixedOffset>,
+    display: &mut Display,
+) {
+    let fonts = &assets.fonts;
+    let forecast = &remote.forecast;
+    let (width, height) = display.panel.size();
+    let edge = UnsignedCoord::new(width - 1, height - 1);
+
+    clock::draw_clock(display, fonts, &assets.locale, &layout.clock, now);
+
+    match &config.worldclock {
+        // the date line is the column left of the weather icons
+        Some(worldclock) if worldclock.replace_date => worldclock::draw_worldclock(
+            display,
+            &fonts.caption,
+            &assets.locale,
+            worldclock,
+            now,
+            Clip::new(UnsignedCoord::new(0, 0), UnsignedCoord::new(85, 19)),
+        ),
+        _ => {
+            let formatted = assets.locale.date(&now);
+            fonts.title.draw(
+                display,
+                &formatted,
+                Coord::new(0, 0),
+                Color::Black,
+                Color::White,
+            );
+        }
+    }
+
+    // the bottom three lines are shared by the headline-sized widgets
+    let bottom = Coord::new(0, 87);
+    display.clipped(
+        Clip::new(bottom.to_unsigned(), edge),
+        |display| match layout.bottom {
+            Widget::News => {
+                if let Some(news) = &remote.news {
+                    news::draw_news(display, fonts, news, bottom, 3);
+                }
+            }
+            Widget::Calendar => {
+                if let Some(events) = &remote.events {
+                    calendar::draw_agenda(display, fonts, &assets.locale, events, now, bottom, 3);
+                }
+            }
+            Widget::HomeAssistant => {
+                if let (Some(ha), Some(states)) = (&config.homeassistant, &live.states) {
+                    homeassistant::draw_states(display, fonts, ha, states, bottom, 3);
+                }
+            }
+            Widget::Todo => {
+                if let Some(todo) = &config.todo {
+                    match todo::load(&todo.path) {
+                        Ok(items) => {
+                            todo::draw_todo(display, fonts, &assets.locale, &items, now, bottom, 3)
+                        }
+                        Err(e) => eprintln!("unable to read {}: {}", todo.path.display(), e),
+                    }
+                }
+            }
+            Widget::Image => {
+                if let Some(image) = &config.image {
+                    let size = edge - bottom.to_unsigned() + UnsignedCoord::new(1, 1);
+                    match dither::load(&image.path, size[0], size[1], image.fit, image.dither) {
+                        Ok(bitmap) => bitmap.draw(display, bottom),
+                        Err(e) => eprintln!("unable to read {}: {}", image.path.display(), e),
+                    }
+                }
+            }
+            Widget::Countdown => {
+                if let Some(countdown) = &config.countdown {
+                    let targets = countdown::targets(
+                        countdown,
+                        &assets.locale,
+                        remote.events.as_deref(),
+                        now.with_timezone(&Utc),
+                    );
+                    countdown::draw_countdown(
+                        display,
+                        fonts,
+                        &assets.locale,
+                        &targets,
+                        now,
+                        bottom,
+                        3,
+                    );
+                }
+            }
+            Widget::WorldClock => {
+                if let Some(worldclock) = &config.worldclock {
+                    worldclock::draw_worldclock(
+                        display,
+                        &fonts.body,
+                        &assets.locale,
+                        worldclock,
+                        now,
+                        Clip::new(bottom.to_unsigned(), edge),
+                    );
+                }
+            }
+        },
+    );
+
+    // the forecast summary wraps downwards, keep it above the bottom lines
+    display.clipped(
+        Clip::new(UnsignedCoord::new(86, 0), UnsignedCoord::new(edge[0], 86)),
+        |display| weather::draw_weather(display, fonts, &assets.locale, forecast, &assets.icons),
+    );
+
+    if let (Some(mqtt), Some(readings)) = (&config.mqtt, &live.readings) {
+        let outdoor = forecast.currently.as_ref().and_then(|c| c.temperature);
+        display.clipped(
+            Clip::new(UnsignedCoord::new(0, 52), UnsignedCoord::new(85, 86)),
+            |display| {
+                sensors::draw_sensors(display, fonts, mqtt, readings, outdoor, Coord::new(0, 52))
+            },
+        );
+    }
 }
 
-/// A set of flags for a forecast, such as the [`Unit`]s specified or the vector
-/// of [DarkSky] stations reporting.
-///
-/// [`Unit`]: enum.Unit.html
-/// [DarkSky]: https://darksky.net
-#[derive(Clone, Debug, Deserialize, Serialize)]
-#[serde(rename_all = "kebab-case")]
-pub struct Flags {
-    /// A list of DarkSky stations used for the [`Forecast`].
-    ///
-    /// [`Forecast`]: struct.Forecast.html
-    pub darksky_stations: Option<Vec<String>>,
-    /// A list of the unavailable DarkSky stations.
-    pub darksky_unavailable: Option<String>,
-    /// A list of the
-    pub datapoint_stations: Option<Vec<String>>,
-    /// A list of [ISD] stations used.
-    ///
-    /// [ISD]: https://www.ncdc.noaa.gov/isd
-    pub isd_stations: Option<Vec<String>>,
-    /// A list of [LAMP] stations used to obtain the information.
-    ///
-    /// [LAMP]: http://www.nws.noaa.gov/mdl/lamp/lamp_info.shtml
-    pub lamp_stations: Option<Vec<String>>,
-    /// A list of [METAR] stations used to obtain the information.
-    ///
-    /// [METAR]: https://www.aviationweather.gov/metar
-    pub metar_stations: Option<Vec<String>>,
-    /// The [METNO license] used.
-    ///
-    /// [METNO license]: http://www.met.no/
-    pub metno_license: Option<String>,
-    /// A list of sources used to obtain the information.
-    pub sources: Option<Vec<String>>,
-    /// The [`Unit`]s used to format the data.
-    ///
-    /// [`Unit`]: enum.Unit.html
-    pub units: Option<String>,
+/// Waits for a send started by [`Screen::send`].
+async fn sent(
+    sending: JoinHandle<Result<(), Box<dyn std::error::Error + Send + Sync>>>,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    sending.await.unwrap_or_else(|e| Err(e.into()))
 }
 
-/// A block of data within a [`Forecast`], with potentially many [`Datapoint`]s.
+/// Sends a frame to the device: a big endian length followed by the buffer.
 ///
-/// [`Datapoint`]: struct.Datapoint.html
-/// [`Forecast`]: struct.Forecast.html
-#[derive(Clone, Debug, Deserialize, Serialize)]
-pub struct Datablock {
-    /// The data for the datablock, if there is any data available.
-    pub data: Option<Vec<Datapoint>>,
-    /// The icon representing the weather type for the datablock.
-    pub icon: Option<Icon>,
-    /// A written summary of the datablock's expected weather.
-    pub summary: Option<String>,
+/// A tri-color frame carries the black plane then the red plane, the device
+/// tells them apart by the length being twice that of a single plane.
+fn push(port: &mut impl Write, frame: &[u8]) -> std::io::Result<()> {
+    let mut buff = Vec::new();
+    buff.write_u32::<BigEndian>(frame.len() as u32)?;
+    port.write_all(&buff)?;
+    port.write_all(frame)
 }
@@ -0,0 +1,52 @@
+//! Headlines from the Yahoo news RSS feed.
+
+use crate::{fonts::Fonts, Color, Display, HttpsClient};
+use bytes::buf::BufExt as _;
+use embedded_graphics::prelude::*;
+use htmlescape::decode_html;
+use rss::Channel;
+use textwrap::fill;
+
+pub async fn fetch(
+    client: &HttpsClient,
+) -> Result<Channel, Box<dyn std::error::Error + Send + Sync>> {
+    let news_resp = client
+        .get("https://news.yahoo.com/rss/home".parse()?)
+        .await?;
+    let news_body = hyper::body::aggregate(news_resp).await?;
+    Ok(Channel::read_from(news_body.reader())?)
+}
+
+/// Draws the first headlines, wrapped to the panel width, for at most
+/// `lines` lines starting at `origin`.
+pub fn draw_news(
+    display: &mut Display,
+    fonts: &Fonts,
+    news: &Channel,
+    origin: Coord,
+    lines: usize,
+) {
+    let line_height = fonts.body.height() as i32;
+    let width = display.clip().bottom_right[0] as i32 + 1 - origin[0];
+    let columns = fonts.body.columns(width.max(0) as u32);
+    let mut count = 0;
+    for titles in news.items().iter().take(2) {
+        let decoded = match titles.title().map(decode_html) {
+            Some(Ok(decoded)) => decoded,
+            _ => continue,
+        };
+        let text = fill(&decoded, columns);
+        for line in text.split('\n') {
+            if count < lines {
+                fonts.body.draw(
+                    display,
+                    line,
+                    origin + Coord::new(0, count as i32 * line_height),
+                    Color::Black,
+                    Color::White,
+                );
+            }
+            count += 1;
+        }
+    }
+}
@@ -0,0 +1,128 @@
+//! Quiet hours: overnight, or whenever nobody is looking, the panel shows a
+//! sleep screen with the forecast for when it wakes and isn't refreshed
+//! again until the period ends.
+
+use crate::{
+    config::QuietPeriod,
+    fonts::Fonts,
+    icons::Icons,
+    locale::Locale,
+    weather::{self, Forecast},
+    Color, Display,
+};
+use chrono::{DateTime, Datelike, Duration, FixedOffset, NaiveDateTime, Weekday};
+use embedded_graphics::prelude::*;
+use textwrap::fill;
+
+fn on(period: &QuietPeriod, day: Weekday) -> bool {
+    period.days.is_empty() || period.days.iter().any(|d| d.0 == day)
+}
+
+fn active(period: &QuietPeriod, now: NaiveDateTime) -> bool {
+    let time = now.time();
+    if !period.from.contains(period.until, time) {
+        return false;
+    }
+    // past midnight the period belongs to the day it started on
+    if period.from.0 <= period.until.0 || time >= period.from.0 {
+        on(period, now.weekday())
+    } else {
+        on(period, now.weekday().pred())
+    }
+}
+
+/// The quiet period in force at `now`, if any.
+pub fn current(periods: &[QuietPeriod], now: DateTime<FixedOffset>) -> Option<&QuietPeriod> {
+    periods
+        .iter()
+        .find(|period| active(period, now.naive_local()))
+}
+
+/// Seconds until `period`, which is in force, ends.
+pub fn until_end(period: &QuietPeriod, now: DateTime<FixedOffset>) -> u64 {
+    period.until.seconds_from(now.time())
+}
+
+/// Seconds until the next quiet period starts, if one is configured.
+pub fn until_start(periods: &[QuietPeriod], now: DateTime<FixedOffset>) -> Option<u64> {
+    let now = now.naive_local();
+    periods
+        .iter()
+        .filter_map(|period| {
+            (0..=7)
+                .map(|days| now.date() + Duration::days(days))
+                .filter(|date| on(period, date.weekday()))
+                .map(|date| date.and_time(period.from.0))
+                .find(|&start| start > now)
+        })
+        .map(|start| (start - now).num_seconds() as u64)
+        .min()
+}
+
+/// Draws the date the panel wakes on with that day's forecast, and when
+/// it will wake.
+pub fn draw_sleep(
+    display: &mut Display,
+    fonts: &Fonts,
+    locale: &Locale,
+    icons: &Icons,
+    forecast: &Forecast,
+    wake: DateTime<FixedOffset>,
+) {
+    let (width, height) = display.panel.size();
+    fonts.title.draw(
+        display,
+        &locale.date(&wake),
+        Coord::new(0, 0),
+        Color::Black,
+        Color::White,
+    );
+
+    let morning = forecast
+        .daily
+        .as_ref()
+        .and_then(|daily| daily.data.as_ref())
+        .and_then(|data| {
+            data.iter().find(|day| {
+                forecast
+                    .local_time(day.time)
+                    .is_some_and(|t| t.date_naive() == wake.date_naive())
+            })
+        });
+    if let Some(day) = morning {
+        let top = fonts.title.height() as i32 + 4;
+        let mut left = 0;
+        if let Some(name) = weather::icon_name(day.icon.as_ref()) {
+            icons.draw(display, name, Coord::new(0, top));
+            left = 44;
+        }
+        let mut lines = Vec::new();
+        if let (Some(high), Some(low)) = (day.temperature_high, day.temperature_low) {
+            lines.push(format!("high {:.0}° low {:.0}°", high, low));
+        }
+        if let Some(summary) = &day.summary {
+            let columns = fonts.body.columns((width as i32 - left).max(0) as u32);
+            lines.extend(
+                fill(summary, columns.max(8))
+                    .split('\n')
+                    .map(str::to_string),
+            );
+        }
+        let line_height = fonts.body.height() as i32;
+        for (i, line) in lines.iter().enumerate() {
+            let origin = Coord::new(left, top + i as i32 * line_height);
+            fonts
+                .body
+                .draw(display, line, origin, Color::Black, Color::White);
+        }
+    }
+
+    let until = format!("quiet until {}", locale.time(&wake));
+    let origin = Coord::new(
+        width as i32 - fonts.caption.width(&until) as i32,
+        height as i32 - fonts.caption.height() as i32,
+    );
+    fonts
+        .caption
+        .draw(display, &until, origin, Color::Black, Color::White);
+}
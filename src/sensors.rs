@@ -0,0 +1,408 @@
+//! Indoor sensor readings received over MQTT.
+//!
+//! This is a deliberately small MQTT 3.1.1 client: it connects, subscribes
+//! to the configured topics at QoS 0 and records every PUBLISH it is sent.
+//! That covers a local Mosquitto broker fed by zigbee2mqtt, Tasmota or
+//! ESPHome sensors without pulling in a full client.
+
+use crate::{
+    config::{MqttConfig, SensorConfig, SensorKind},
+    fonts::Fonts,
+    Color, Display,
+};
+use embedded_graphics::prelude::*;
+use std::{
+    collections::HashMap,
+    io::{self, ErrorKind},
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+use tokio::{
+    net::TcpStream,
+    prelude::*,
+    time::{self, delay_for, timeout},
+};
+
+const KEEP_ALIVE_SECS: u16 = 60;
+const RECONNECT_DELAY: Duration = Duration::from_secs(30);
+
+#[derive(Clone, Copy, Debug)]
+pub struct Reading {
+    pub value: f64,
+    pub received: Instant,
+}
+
+/// Latest reading per topic and JSON field, shared with the subscription
+/// task.
+pub type Readings = Arc<Mutex<HashMap<(String, Option<String>), Reading>>>;
+
+fn key(sensor: &SensorConfig) -> (String, Option<String>) {
+    (sensor.topic.clone(), sensor.field.clone())
+}
+
+/// Starts a background task that keeps a subscription open, reconnecting
+/// whenever the broker goes away. Without any sensors there is nothing to
+/// subscribe to, and an empty SUBSCRIBE would only get the connection
+/// dropped, so no task is started.
+pub fn subscribe(config: MqttConfig) -> Readings {
+    let readings = Readings::default();
+    if config.sensors.is_empty() {
+        eprintln!(
+            "mqtt {}:{}: no sensors configured, not connecting",
+            config.host, config.port
+        );
+        return readings;
+    }
+    let shared = readings.clone();
+    tokio::spawn(async move {
+        loop {
+            if let Err(e) = session(&config, &shared).await {
+                eprintln!("mqtt {}:{}: {}", config.host, config.port, e);
+            }
+            delay_for(RECONNECT_DELAY).await;
+        }
+    });
+    readings
+}
+
+fn put_str(buf: &mut Vec<u8>, s: &str) {
+    buf.extend_from_slice(&(s.len() as u16).to_be_bytes());
+    buf.extend_from_slice(s.as_bytes());
+}
+
+/// Frames a control packet, encoding the variable length "remaining length".
+fn packet(header: u8, body: &[u8]) -> Vec<u8> {
+    let mut out = vec![header];
+    let mut len = body.len();
+    loop {
+        let mut byte = (len % 128) as u8;
+        len /= 128;
+        if len > 0 {
+            byte |= 0x80;
+        }
+        out.push(byte);
+        if len == 0 {
+            break;
+        }
+    }
+    out.extend_from_slice(body);
+    out
+}
+
+/// Splits the first complete packet off the front of `buf`, returning its
+/// header byte and body, or `None` until all of it has arrived.
+fn take_packet(buf: &mut Vec<u8>) -> io::Result<Option<(u8, Vec<u8>)>> {
+    let mut len = 0usize;
+    let mut i = 1;
+    loop {
+        // the remaining length is at most four bytes long
+        if i > 4 {
+            return Err(io::Error::new(
+                ErrorKind::InvalidData,
+                "packet length longer than four bytes",
+            ));
+        }
+        let byte = match buf.get(i) {
+            Some(&byte) => byte,
+            None => return Ok(None),
+        };
+        len += ((byte & 0x7f) as usize) << (7 * (i - 1));
+        i += 1;
+        if byte & 0x80 == 0 {
+            break;
+        }
+    }
+    if buf.len() < i + len {
+        return Ok(None);
+    }
+    let header = buf[0];
+    let body = buf[i..i + len].to_vec();
+    buf.drain(..i + len);
+    Ok(Some((header, body)))
+}
+
+fn connect_packet(config: &MqttConfig) -> Vec<u8> {
+    let mut body = Vec::new();
+    put_str(&mut body, "MQTT");
+    body.push(4);
+    let mut flags = 0x02; // clean session
+    if config.username.is_some() {
+        flags |= 0x80;
+        if config.password.is_some() {
+            flags |= 0x40;
+        }
+    }
+    body.push(flags);
+    body.extend_from_slice(&KEEP_ALIVE_SECS.to_be_bytes());
+    put_str(&mut body, &config.client_id);
+    if let Some(username) = &config.username {
+        put_str(&mut body, username);
+        if let Some(password) = &config.password {
+            put_str(&mut body, password);
+        }
+    }
+    packet(0x10, &body)
+}
+
+fn subscribe_packet(sensors: &[SensorConfig]) -> Vec<u8> {
+    let mut body = 1u16.to_be_bytes().to_vec();
+    let mut topics: Vec<&str> = sensors.iter().map(|s| s.topic.as_str()).collect();
+    topics.sort_unstable();
+    topics.dedup();
+    for topic in topics {
+        put_str(&mut body, topic);
+        body.push(0);
+    }
+    packet(0x82, &body)
+}
+
+/// Pulls a number out of a payload, either a bare value or, when the sensor
+/// names a `field`, a member of a JSON object.
+fn parse_value(payload: &[u8], field: Option<&str>) -> Option<f64> {
+    match field {
+        Some(field) => {
+            let json: serde_json::Value = serde_json::from_slice(payload).ok()?;
+            json.get(field)?.as_f64()
+        }
+        None => std::str::from_utf8(payload).ok()?.trim().parse().ok(),
+    }
+}
+
+fn handle_publish(header: u8, body: &[u8], config: &MqttConfig, readings: &Readings) {
+    if body.len() < 2 {
+        return;
+    }
+    let topic_len = u16::from_be_bytes([body[0], body[1]]) as usize;
+    let topic = match body.get(2..2 + topic_len).map(std::str::from_utf8) {
+        Some(Ok(topic)) => topic,
+        _ => return,
+    };
+    let mut payload = &body[2 + topic_len..];
+    // a packet identifier follows the topic for QoS 1 and 2
+    if (header >> 1) & 0x03 > 0 {
+        payload = payload.get(2..).unwrap_or_default();
+    }
+    for sensor in config.sensors.iter().filter(|s| s.topic == topic) {
+        if let Some(value) = parse_value(payload, sensor.field.as_deref()) {
+            readings.lock().unwrap().insert(
+                key(sensor),
+                Reading {
+                    value,
+                    received: Instant::now(),
+                },
+            );
+        }
+    }
+}
+
+enum Wake {
+    Read(usize),
+    Ping,
+}
+
+async fn session(config: &MqttConfig, readings: &Readings) -> io::Result<()> {
+    let mut stream = TcpStream::connect((config.host.as_str(), config.port)).await?;
+    stream.write_all(&connect_packet(config)).await?;
+
+    let mut buf = Vec::new();
+    let mut chunk = [0u8; 1024];
+    let n = timeout(Duration::from_secs(10), stream.read(&mut chunk))
+        .await
+        .map_err(|_| io::Error::new(ErrorKind::TimedOut, "no CONNACK from broker"))??;
+    buf.extend_from_slice(&chunk[..n]);
+    match take_packet(&mut buf)? {
+        Some((0x20, body)) if body.get(1) == Some(&0) => {}
+        Some((0x20, body)) => {
+            return Err(io::Error::new(
+                ErrorKind::ConnectionRefused,
+                format!("broker refused connection, code {:?}", body.get(1)),
+            ))
+        }
+        _ => return Err(io::Error::new(ErrorKind::InvalidData, "expected CONNACK")),
+    }
+    stream.write_all(&subscribe_packet(&config.sensors)).await?;
+
+    // nothing else is ever sent, so ping on a fixed schedule however busy
+    // the broker is
+    let ping_every = Duration::from_secs(u64::from(KEEP_ALIVE_SECS) / 2);
+    let mut ping = time::interval_at(time::Instant::now() + ping_every, ping_every);
+    loop {
+        let wake = tokio::select! {
+            n = stream.read(&mut chunk) => Wake::Read(n?),
+            _ = ping.tick() => Wake::Ping,
+        };
+        match wake {
+            Wake::Read(0) => return Err(ErrorKind::UnexpectedEof.into()),
+            Wake::Read(n) => buf.extend_from_slice(&chunk[..n]),
+            Wake::Ping => stream.write_all(&[0xc0, 0]).await?,
+        }
+        while let Some((header, body)) = take_packet(&mut buf)? {
+            if header & 0xf0 == 0x30 {
+                handle_publish(header, &body, config, readings);
+            }
+        }
+    }
+}
+
+fn format_value(sensor: &SensorConfig, value: Option<f64>) -> String {
+    let label = sensor.label.as_deref().unwrap_or("");
+    let value = match value {
+        Some(value) => value,
+        None => return format!("{}--", label),
+    };
+    match sensor.kind {
+        SensorKind::Temperature => format!("{}{:.0}°", label, value),
+        SensorKind::Humidity => format!("{}{:.0}%", label, value),
+        SensorKind::Co2 => format!("{}{:.0}ppm", label, value),
+        SensorKind::Other => format!(
+            "{}{:.1}{}",
+            label,
+            value,
+            sensor.unit.as_deref().unwrap_or("")
+        ),
+    }
+}
+
+/// Draws the indoor readings below the clock.
+///
+/// The first temperature sensor is paired with the outdoor temperature as
+/// `in 70° out 75°`, everything else follows two to a line. Readings older
+/// than `stale_minutes` are shown as `--`.
+pub fn draw_sensors(
+    display: &mut Display,
+    fonts: &Fonts,
+    config: &MqttConfig,
+    readings: &Readings,
+    outdoor: Option<f64>,
+    origin: Coord,
+) {
+    let readings = readings.lock().unwrap();
+    let stale = Duration::from_secs(config.stale_minutes * 60);
+    let value = |sensor: &SensorConfig| {
+        readings
+            .get(&key(sensor))
+            .filter(|r| r.received.elapsed() < stale)
+            .map(|r| r.value)
+    };
+
+    let mut lines = Vec::new();
+    let indoor = config
+        .sensors
+        .iter()
+        .position(|s| s.kind == SensorKind::Temperature);
+    if let Some(i) = indoor {
+        let inside = value(&config.sensors[i]).map_or("--".to_string(), |t| format!("{:.0}°", t));
+        let outside = outdoor.map_or("--".to_string(), |t| format!("{:.0}°", t));
+        lines.push(format!("in {} out {}", inside, outside));
+    }
+    let rest: Vec<String> = config
+        .sensors
+        .iter()
+        .enumerate()
+        .filter(|&(j, _)| Some(j) != indoor)
+        .map(|(_, sensor)| format_value(sensor, value(sensor)))
+        .collect();
+    for pair in rest.chunks(2) {
+        lines.push(pair.join(" "));
+    }
+
+    // the column left of the weather icons
+    let width = display.clip().bottom_right[0] as i32 + 1 - origin[0];
+    for (i, line) in lines.iter().take(3).enumerate() {
+        let line = fonts.body.fit(line, width.max(0) as u32);
+        fonts.body.draw(
+            display,
+            &line,
+            origin + Coord::new(0, i as i32 * fonts.body.height() as i32),
+            Color::Black,
+            Color::White,
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(username: Option<&str>, password: Option<&str>) -> MqttConfig {
+        MqttConfig {
+            host: "localhost".to_string(),
+            port: 1883,
+            client_id: "screen".to_string(),
+            username: username.map(str::to_string),
+            password: password.map(str::to_string),
+            stale_minutes: 15,
+            sensors: Vec::new(),
+        }
+    }
+
+    fn sensor(topic: &str) -> SensorConfig {
+        SensorConfig {
+            topic: topic.to_string(),
+            kind: SensorKind::Other,
+            label: None,
+            field: None,
+            unit: None,
+        }
+    }
+
+    #[test]
+    fn connect_without_credentials() {
+        let packet = connect_packet(&config(None, None));
+        let mut expected = vec![0x10, 18, 0, 4];
+        expected.extend_from_slice(b"MQTT");
+        expected.extend_from_slice(&[4, 0x02, 0, 60, 0, 6]);
+        expected.extend_from_slice(b"screen");
+        assert_eq!(packet, expected);
+    }
+
+    #[test]
+    fn connect_with_credentials() {
+        let packet = connect_packet(&config(Some("me"), Some("pw")));
+        // clean session, username and password flags
+        assert_eq!(packet[9], 0xc2);
+        assert_eq!(packet[1] as usize, packet.len() - 2);
+        assert!(packet.ends_with(&[0, 2, b'm', b'e', 0, 2, b'p', b'w']));
+    }
+
+    #[test]
+    fn subscribe_lists_each_topic_once() {
+        let packet = subscribe_packet(&[sensor("b"), sensor("a"), sensor("b")]);
+        assert_eq!(packet, [0x82, 10, 0, 1, 0, 1, b'a', 0, 0, 1, b'b', 0]);
+    }
+
+    #[test]
+    fn takes_whole_packets_only() {
+        let mut buf = vec![0x30, 3, 1, 2];
+        assert_eq!(take_packet(&mut buf).unwrap(), None);
+        buf.extend_from_slice(&[3, 0xd0, 0]);
+        assert_eq!(take_packet(&mut buf).unwrap(), Some((0x30, vec![1, 2, 3])));
+        assert_eq!(take_packet(&mut buf).unwrap(), Some((0xd0, vec![])));
+        assert!(buf.is_empty());
+    }
+
+    #[test]
+    fn takes_long_packets() {
+        let body = vec![7u8; 200];
+        let mut buf = packet(0x30, &body);
+        assert_eq!(&buf[1..3], [0xc8, 0x01]);
+        assert_eq!(take_packet(&mut buf).unwrap(), Some((0x30, body)));
+    }
+
+    #[test]
+    fn rejects_overlong_lengths() {
+        let mut buf = vec![0x30, 0xff, 0xff, 0xff, 0xff, 0x01];
+        assert!(take_packet(&mut buf).is_err());
+    }
+
+    #[test]
+    fn parses_values() {
+        assert_eq!(parse_value(b" 21.5\n", None), Some(21.5));
+        assert_eq!(parse_value(b"on", None), None);
+        let json = br#"{"temperature": 22.25, "battery": "low"}"#;
+        assert_eq!(parse_value(json, Some("temperature")), Some(22.25));
+        assert_eq!(parse_value(json, Some("battery")), None);
+        assert_eq!(parse_value(json, Some("humidity")), None);
+        assert_eq!(parse_value(b"22", Some("temperature")), None);
+    }
+}
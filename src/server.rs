@@ -0,0 +1,246 @@
+//! Serving frames to displays that poll instead of being pushed to.
+//!
+//! Boards that deep sleep between updates wake up, `GET /frame.bin` and go
+//! back to sleep. The ETag lets them send `If-None-Match` and skip the
+//! refresh, and the radio time, when nothing has changed since last time.
+//! `X-Next-Wake` says how many seconds until the frame is next expected to
+//! change, so they can sleep until then instead of polling blindly.
+
+use crate::{
+    calendar,
+    config::{ClockStyle, DisplayConfig, Widget},
+    fetch, quiet, slideshow, Config, Dashboard, Display, Layout,
+};
+use chrono::{DateTime, Duration as Span, FixedOffset, Timelike, Utc};
+use hyper::{
+    header,
+    service::{make_service_fn, service_fn},
+    Body, Request, Response, Server, StatusCode,
+};
+use image::{DynamicImage, ImageOutputFormat};
+use std::{
+    collections::hash_map::DefaultHasher,
+    convert::Infallible,
+    hash::{Hash, Hasher},
+    net::SocketAddr,
+    sync::Arc,
+    time::Duration,
+};
+use tokio::{sync::RwLock, time::delay_for};
+
+type Error = Box<dyn std::error::Error + Send + Sync>;
+
+const NEXT_WAKE: &str = "x-next-wake";
+
+/// The shared data, and the displays that can be asked for by name.
+struct State {
+    dashboard: RwLock<Dashboard>,
+    displays: Vec<DisplayConfig>,
+    /// When the background fetch will next run.
+    next_fetch: RwLock<DateTime<Utc>>,
+}
+
+/// Answers frame requests on `listen` until the process is stopped,
+/// fetching in the background every `fetch_minutes`.
+pub async fn serve(config: Config, listen: &str) -> Result<(), Error> {
+    let addr: SocketAddr = listen
+        .parse()
+        .map_err(|e| format!("invalid listen address {}: {}", listen, e))?;
+    let fetch_every = Duration::from_secs(
+        config
+            .daemon
+            .as_ref()
+            .map_or(crate::config::default_fetch_minutes(), |d| d.fetch_minutes)
+            * 60,
+    );
+    let state = Arc::new(State {
+        displays: config.displays(),
+        next_fetch: RwLock::new(Utc::now() + Span::seconds(fetch_every.as_secs() as i64)),
+        dashboard: RwLock::new(Dashboard::start(config).await?),
+    });
+
+    let refresh = state.clone();
+    tokio::spawn(async move {
+        loop {
+            delay_for(fetch_every).await;
+            // fetch under the read lock so requests aren't held up
+            let fetched = {
+                let dashboard = refresh.dashboard.read().await;
+                fetch(
+                    &dashboard.client,
+                    &dashboard.config,
+                    &dashboard.assets.locale,
+                    &dashboard.live,
+                    dashboard.news,
+                )
+                .await
+            };
+            *refresh.next_fetch.write().await =
+                Utc::now() + Span::seconds(fetch_every.as_secs() as i64);
+            match fetched {
+                Ok(remote) => refresh.dashboard.write().await.remote = remote,
+                Err(e) => eprintln!("unable to refresh data, keeping the last copy: {}", e),
+            }
+        }
+    });
+
+    let make_svc = make_service_fn(move |_| {
+        let state = state.clone();
+        async move {
+            Ok::<_, Infallible>(service_fn(move |req| {
+                let state = state.clone();
+                async move { Ok::<_, Infallible>(respond(&state, &req).await) }
+            }))
+        }
+    });
+    eprintln!("serving frames on http://{}/frame.bin", addr);
+    Server::bind(&addr).serve(make_svc).await?;
+    Ok(())
+}
+
+fn status(code: StatusCode, message: String) -> Response<Body> {
+    let mut resp = Response::new(Body::from(message));
+    *resp.status_mut() = code;
+    resp
+}
+
+async fn respond(state: &State, req: &Request<Body>) -> Response<Body> {
+    let png = match req.uri().path() {
+        "/frame.bin" => false,
+        "/frame.png" => true,
+        path => return status(StatusCode::NOT_FOUND, format!("no such page {}\n", path)),
+    };
+    let device = req
+        .uri()
+        .query()
+        .unwrap_or_default()
+        .split('&')
+        .find_map(|pair| pair.strip_prefix("device="));
+    let display = match device {
+        Some(name) => state.displays.iter().find(|d| d.name == name),
+        None => state.displays.first(),
+    };
+    let display = match display {
+        Some(display) => display,
+        None => {
+            let name = device.unwrap_or_default();
+            return status(
+                StatusCode::NOT_FOUND,
+                format!("no display named {}\n", name),
+            );
+        }
+    };
+    let panel = match display.panel.panel() {
+        Ok(panel) => panel,
+        Err(e) => return status(StatusCode::INTERNAL_SERVER_ERROR, e + "\n"),
+    };
+
+    let next_fetch = *state.next_fetch.read().await;
+    let mut buf = panel.blank();
+    let wake = {
+        let dashboard = state.dashboard.read().await;
+        let layout = Layout::new(&dashboard.config, display);
+        let mut display = Display::new(&mut buf, panel);
+        let now = dashboard.assets.locale.now();
+        dashboard.draw(&layout, now, &mut display);
+        next_wake(&dashboard, &layout, now, next_fetch)
+    };
+
+    // the same frame gives the same tag, so an unchanged screen is a 304
+    let mut hasher = DefaultHasher::new();
+    buf.hash(&mut hasher);
+    let etag = format!(
+        "\"{:016x}{}\"",
+        hasher.finish(),
+        if png { "-png" } else { "" }
+    );
+    let unchanged = req
+        .headers()
+        .get(header::IF_NONE_MATCH)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|tags| tags.split(',').any(|tag| tag.trim() == etag));
+    if unchanged {
+        let mut resp = status(StatusCode::NOT_MODIFIED, String::new());
+        let headers = resp.headers_mut();
+        headers.insert(header::ETAG, etag.parse().expect("tag is ascii"));
+        headers.insert(NEXT_WAKE, wake.into());
+        return resp;
+    }
+
+    let (content_type, body) = if png {
+        let mut encoded = Vec::new();
+        let image = DynamicImage::ImageRgb8(panel.preview(&buf));
+        if let Err(e) = image.write_to(&mut encoded, ImageOutputFormat::Png) {
+            return status(StatusCode::INTERNAL_SERVER_ERROR, format!("{}\n", e));
+        }
+        ("image/png", encoded)
+    } else {
+        ("application/octet-stream", buf)
+    };
+    Response::builder()
+        .header(header::CONTENT_TYPE, content_type)
+        .header(header::ETAG, etag)
+        .header(header::CACHE_CONTROL, "no-cache")
+        .header(NEXT_WAKE, wake)
+        .body(Body::from(body))
+        .expect("headers are valid")
+}
+
+/// Seconds until the frame for `layout` is next expected to change: the
+/// end of quiet hours while they last, then the next minute while a clock
+/// is showing, otherwise the next slide, agenda change, midnight, weather
+/// fetch or start of quiet hours, whichever comes first.
+fn next_wake(
+    dashboard: &Dashboard,
+    layout: &Layout,
+    now: DateTime<FixedOffset>,
+    next_fetch: DateTime<Utc>,
+) -> u64 {
+    let config = &dashboard.config;
+    let now_utc = now.with_timezone(&Utc);
+    let minute = 60 - u64::from(now.second());
+    let mut wake = (next_fetch - now_utc).num_seconds().max(0) as u64;
+
+    match quiet::current(&config.quiet, now) {
+        Some(period) => return quiet::until_end(period, now).max(1),
+        None => {
+            if let Some(start) = quiet::until_start(&config.quiet, now) {
+                wake = wake.min(start);
+            }
+        }
+    }
+
+    let slideshow = config.slideshow.as_ref().filter(|s| s.active(now.time()));
+    if let Some(slideshow) = slideshow {
+        wake = wake.min(slideshow::until_next(slideshow, now));
+        if slideshow.clock {
+            wake = wake.min(minute);
+        }
+        return wake.max(1);
+    }
+    if let Some(start) = config
+        .slideshow
+        .as_ref()
+        .and_then(|s| slideshow::until_start(s, now))
+    {
+        wake = wake.min(start);
+    }
+
+    let worldclock = config.worldclock.as_ref().is_some_and(|w| w.replace_date);
+    match layout.clock.style {
+        ClockStyle::Hidden => {}
+        _ if layout.clock.seconds => wake = 1,
+        _ => wake = wake.min(minute),
+    }
+    if worldclock || matches!(layout.bottom, Widget::WorldClock | Widget::Countdown) {
+        wake = wake.min(minute);
+    }
+    if let (Widget::Calendar, Some(events)) = (layout.bottom, &dashboard.remote.events) {
+        if let Some(change) = calendar::next_change(events, now_utc) {
+            wake = wake.min((change - now_utc).num_seconds().max(0) as u64);
+        }
+    }
+    // the date line, and anything saying "today"
+    let midnight = u64::from(24 * 60 * 60 - now.num_seconds_from_midnight());
+    wake.min(midnight).max(1)
+}
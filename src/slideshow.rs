@@ -0,0 +1,85 @@
+//! Photo frame mode: pictures from a directory in rotation, full screen.
+
+use crate::{config::SlideshowConfig, dither, fonts::Fonts, locale::Locale, Color, Display};
+use chrono::{DateTime, FixedOffset};
+use embedded_graphics::{prelude::*, primitives::Rectangle, Drawing};
+use std::{
+    io,
+    path::{Path, PathBuf},
+};
+
+/// The pictures in `dir`, sorted by name.
+pub fn images(dir: &Path) -> io::Result<Vec<PathBuf>> {
+    let mut images: Vec<PathBuf> = std::fs::read_dir(dir)?
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|path| {
+            path.extension()
+                .and_then(|ext| ext.to_str())
+                .is_some_and(|ext| {
+                    ["png", "jpg", "jpeg", "bmp"]
+                        .iter()
+                        .any(|known| ext.eq_ignore_ascii_case(known))
+                })
+        })
+        .collect();
+    images.sort();
+    Ok(images)
+}
+
+/// Seconds until the next picture is due, or the dashboard comes back, so a
+/// slideshow without a clock only redraws when something changes.
+pub fn until_next(config: &SlideshowConfig, now: DateTime<FixedOffset>) -> u64 {
+    let interval = config.interval_minutes.max(1) * 60;
+    let next = interval - now.timestamp() as u64 % interval;
+    let end = config.until.map(|until| until.seconds_from(now.time()));
+    end.map_or(next, |end| next.min(end))
+}
+
+/// Seconds until a scheduled slideshow takes over from the dashboard.
+pub fn until_start(config: &SlideshowConfig, now: DateTime<FixedOffset>) -> Option<u64> {
+    config.from.map(|from| from.seconds_from(now.time()))
+}
+
+/// Draws the picture for the current interval, counted from the epoch so
+/// the rotation carries on across restarts.
+pub fn draw_slide(
+    display: &mut Display,
+    fonts: &Fonts,
+    locale: &Locale,
+    config: &SlideshowConfig,
+    now: DateTime<FixedOffset>,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let images = images(&config.directory)?;
+    if images.is_empty() {
+        return Err(format!("no pictures in {}", config.directory.display()).into());
+    }
+    let slot = now.timestamp() as u64 / (config.interval_minutes.max(1) * 60);
+    let path = &images[(slot % images.len() as u64) as usize];
+    let (width, height) = display.panel.size();
+    dither::load(path, width, height, config.fit, config.dither)?.draw(display, Coord::new(0, 0));
+
+    if config.clock {
+        // leave a white margin around the digits
+        let formatted = locale.time(&now);
+        let (w, h) = (
+            fonts.huge.width(&formatted) as i32,
+            fonts.huge.height() as i32,
+        );
+        let (right, bottom) = (width as i32 - 1, height as i32 - 1);
+        display.draw(
+            Rectangle::new(
+                Coord::new(right - w - 5, bottom - h - 3),
+                Coord::new(right, bottom),
+            )
+            .fill(Some(Color::White)),
+        );
+        fonts.huge.draw(
+            display,
+            &formatted,
+            Coord::new(right - w - 2, bottom - h - 1),
+            Color::Black,
+            Color::White,
+        );
+    }
+    Ok(())
+}
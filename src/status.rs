@@ -0,0 +1,207 @@
+//! Messages from the board back to the host.
+//!
+//! The firmware writes one line of ASCII per message on the same serial
+//! port frames go down:
+//!
+//! ```text
+//! boot
+//! applied
+//! battery 3.91
+//! temp 22.5
+//! button 1
+//! ```
+//!
+//! `applied` follows each frame once it is on the panel, `battery` is the
+//! cell voltage and `temp` the board's sensor in °C. Anything else is logged
+//! as it is.
+
+use crate::{Color, Display};
+use embedded_graphics::{drawable::Pixel, prelude::*, Drawing};
+use std::{
+    fmt,
+    io::{ErrorKind, Read},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    thread,
+};
+use tokio::sync::mpsc::UnboundedSender;
+
+#[derive(Debug, PartialEq)]
+pub enum Message {
+    Boot,
+    Applied,
+    Battery(f32),
+    Temperature(f32),
+    Button(u8),
+    Other(String),
+}
+
+impl Message {
+    pub fn parse(line: &str) -> Option<Self> {
+        let mut words = line.split_whitespace();
+        let message = match (words.next()?, words.next()) {
+            ("boot", None) => Message::Boot,
+            ("applied", None) => Message::Applied,
+            ("battery", Some(volts)) => volts.parse().map(Message::Battery).ok()?,
+            ("temp", Some(celsius)) => celsius.parse().map(Message::Temperature).ok()?,
+            ("button", Some(button)) => button.parse().map(Message::Button).ok()?,
+            _ => Message::Other(line.trim().to_string()),
+        };
+        Some(message)
+    }
+}
+
+impl fmt::Display for Message {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Message::Boot => write!(f, "booted"),
+            Message::Applied => write!(f, "frame applied"),
+            Message::Battery(volts) => write!(f, "battery {:.2}V", volts),
+            Message::Temperature(celsius) => write!(f, "temperature {:.1}°C", celsius),
+            Message::Button(button) => write!(f, "button {} pressed", button),
+            Message::Other(line) => write!(f, "{:?}", line),
+        }
+    }
+}
+
+/// What the board last told us about itself.
+#[derive(Debug, Default, Clone)]
+pub struct DeviceStatus {
+    /// Cell voltage.
+    pub battery: Option<f32>,
+}
+
+pub type Status = Arc<Mutex<DeviceStatus>>;
+
+/// Reads messages from `port` on a thread of its own, logging each under
+/// `name`, keeping `status` up to date and passing button presses on to
+/// `buttons`, until the port goes away or `open` is cleared. `port` needs a
+/// read timeout for the latter.
+pub fn listen(
+    name: String,
+    mut port: impl Read + Send + 'static,
+    status: Status,
+    buttons: UnboundedSender<u8>,
+    open: Arc<AtomicBool>,
+) {
+    thread::spawn(move || {
+        let mut pending = Vec::new();
+        let mut chunk = [0u8; 64];
+        while open.load(Ordering::Relaxed) {
+            let read = match port.read(&mut chunk) {
+                Ok(0) => break,
+                Ok(read) => read,
+                Err(e) if e.kind() == ErrorKind::TimedOut || e.kind() == ErrorKind::Interrupted => {
+                    continue
+                }
+                Err(_) => break,
+            };
+            pending.extend_from_slice(&chunk[..read]);
+            while let Some(end) = pending.iter().position(|&b| b == b'\n') {
+                let line: Vec<u8> = pending.drain(..=end).collect();
+                let message = match Message::parse(&String::from_utf8_lossy(&line)) {
+                    Some(message) => message,
+                    None => continue,
+                };
+                eprintln!("{}: {}", name, message);
+                match message {
+                    Message::Battery(volts) => status.lock().unwrap().battery = Some(volts),
+                    // nobody listening just means buttons aren't used
+                    Message::Button(button) => {
+                        let _ = buttons.send(button);
+                    }
+                    _ => {}
+                }
+            }
+        }
+    });
+}
+
+/// Rough charge of a lithium cell from its resting voltage.
+fn charge(volts: f32) -> f32 {
+    ((volts - 3.3) / (4.2 - 3.3)).clamp(0.0, 1.0)
+}
+
+/// Draws a 16x8 battery with its top left corner at `origin`, filled to
+/// the charge and red once it is low.
+pub fn draw_battery(display: &mut Display, volts: f32, origin: Coord) {
+    let charge = charge(volts);
+    let fill = (charge * 10.0).round() as i32;
+    let color = if charge < 0.2 {
+        Color::Red
+    } else {
+        Color::Black
+    };
+    let pixels = (0..8)
+        .flat_map(|y| (0..16).map(move |x| (x, y)))
+        .filter_map(|(x, y)| {
+            let color = match (x, y) {
+                // the terminal nub on the right
+                (14..=15, 2..=5) => color,
+                (14..=15, _) => Color::White,
+                (0 | 13, _) | (_, 0 | 7) => color,
+                (2..=11, 2..=5) if x - 2 < fill => color,
+                _ => Color::White,
+            };
+            let p = origin + Coord::new(x, y);
+            if p[0] < 0 || p[1] < 0 {
+                return None;
+            }
+            Some(Pixel(p.to_unsigned(), color))
+        });
+    display.draw(pixels);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_known_messages() {
+        assert_eq!(Message::parse("boot\r\n"), Some(Message::Boot));
+        assert_eq!(Message::parse("applied"), Some(Message::Applied));
+        assert_eq!(Message::parse("battery 3.91"), Some(Message::Battery(3.91)));
+        assert_eq!(
+            Message::parse("temp -2.5"),
+            Some(Message::Temperature(-2.5))
+        );
+        assert_eq!(Message::parse("button 3\n"), Some(Message::Button(3)));
+    }
+
+    #[test]
+    fn keeps_unknown_lines() {
+        assert_eq!(
+            Message::parse(" wifi connected \r\n"),
+            Some(Message::Other("wifi connected".to_string()))
+        );
+        assert_eq!(
+            Message::parse("boot 2"),
+            Some(Message::Other("boot 2".to_string()))
+        );
+        assert_eq!(Message::parse("\r\n"), None);
+    }
+
+    #[test]
+    fn drops_malformed_values() {
+        assert_eq!(
+            Message::parse("battery"),
+            Some(Message::Other("battery".to_string()))
+        );
+        assert_eq!(Message::parse("battery low"), None);
+        assert_eq!(Message::parse("temp 2x"), None);
+        assert_eq!(Message::parse("button -1"), None);
+        assert_eq!(Message::parse("button 300"), None);
+    }
+
+    #[test]
+    fn charge_spans_a_lithium_cell() {
+        assert_eq!(charge(3.3), 0.0);
+        assert_eq!(charge(4.2), 1.0);
+        assert!((charge(3.75) - 0.5).abs() < 1e-6);
+        // clamped outside the range, e.g. while charging
+        assert_eq!(charge(2.9), 0.0);
+        assert_eq!(charge(4.4), 1.0);
+    }
+}
@@ -0,0 +1,231 @@
+//! Todo list widget reading a [todo.txt] file or a Markdown checklist.
+//!
+//! [todo.txt]: https://github.com/todotxt/todo.txt
+
+use crate::{fonts::Fonts, locale::Locale, Color, Display};
+use chrono::{DateTime, FixedOffset, NaiveDate};
+use embedded_graphics::{prelude::*, primitives::Rectangle, Drawing};
+use notify::{watcher, DebouncedEvent, RecursiveMode, Watcher};
+use std::{io, path::Path, sync::mpsc::channel, thread, time::Duration};
+use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver};
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Item {
+    pub text: String,
+    pub done: bool,
+    /// todo.txt style priority, `'A'` being the most important.
+    pub priority: Option<char>,
+    pub due: Option<NaiveDate>,
+}
+
+/// Splits a leading `(A)` priority off a task description.
+fn take_priority(text: &str) -> (Option<char>, &str) {
+    let bytes = text.as_bytes();
+    if bytes.len() >= 3 && bytes[0] == b'(' && bytes[2] == b')' && bytes[1].is_ascii_uppercase() {
+        (Some(bytes[1] as char), text[3..].trim_start())
+    } else {
+        (None, text)
+    }
+}
+
+/// Strips `due:YYYY-MM-DD` out of the description.
+fn take_due(text: &str) -> (Option<NaiveDate>, String) {
+    let mut due = None;
+    let words: Vec<&str> = text
+        .split_whitespace()
+        .filter(|word| match word.strip_prefix("due:") {
+            Some(date) => match NaiveDate::parse_from_str(date, "%Y-%m-%d") {
+                Ok(date) => {
+                    due = Some(date);
+                    false
+                }
+                Err(_) => true,
+            },
+            None => true,
+        })
+        .collect();
+    (due, words.join(" "))
+}
+
+fn is_date(word: &str) -> bool {
+    NaiveDate::parse_from_str(word, "%Y-%m-%d").is_ok()
+}
+
+/// Parses one todo.txt line: `x 2020-03-02 (A) 2020-03-01 text due:2020-03-05`.
+fn parse_todo_txt(line: &str) -> Option<Item> {
+    let line = line.trim();
+    if line.is_empty() {
+        return None;
+    }
+    let (done, mut rest) = match line.strip_prefix("x ") {
+        Some(rest) => (true, rest.trim_start()),
+        None => (false, line),
+    };
+    // completion date on finished tasks
+    if done {
+        if let Some(word) = rest.split_whitespace().next().filter(|w| is_date(w)) {
+            rest = rest[word.len()..].trim_start();
+        }
+    }
+    let (priority, mut rest) = take_priority(rest);
+    // creation date
+    if let Some(word) = rest.split_whitespace().next().filter(|w| is_date(w)) {
+        rest = rest[word.len()..].trim_start();
+    }
+    let (due, text) = take_due(rest);
+    Some(Item {
+        text,
+        done,
+        priority,
+        due,
+    })
+}
+
+/// Parses a Markdown task list item such as `- [ ] (B) call Bob due:2020-03-05`.
+fn parse_markdown(line: &str) -> Option<Item> {
+    let line = line.trim_start();
+    let rest = line
+        .strip_prefix("- ")
+        .or_else(|| line.strip_prefix("* "))
+        .or_else(|| line.strip_prefix("+ "))?
+        .trim_start();
+    let done = match rest.get(..3)? {
+        "[ ]" => false,
+        "[x]" | "[X]" => true,
+        _ => return None,
+    };
+    let (priority, rest) = take_priority(rest[3..].trim());
+    let (due, text) = take_due(rest);
+    Some(Item {
+        text,
+        done,
+        priority,
+        due,
+    })
+}
+
+/// Parses a todo list, treating `.md`/`.markdown` files as checklists and
+/// everything else as todo.txt.
+pub fn parse(path: &Path, text: &str) -> Vec<Item> {
+    let markdown = path
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("md") || ext.eq_ignore_ascii_case("markdown"));
+    text.lines()
+        .filter_map(if markdown {
+            parse_markdown
+        } else {
+            parse_todo_txt
+        })
+        .collect()
+}
+
+/// Reads the list and returns the open items, most urgent first: by
+/// priority, then due date, then file order.
+pub fn load(path: &Path) -> io::Result<Vec<Item>> {
+    let text = std::fs::read_to_string(path)?;
+    let mut items: Vec<Item> = parse(path, &text).into_iter().filter(|i| !i.done).collect();
+    // stable sort keeps file order for ties, `None` sorts after any value
+    items.sort_by_key(|i| {
+        (
+            i.priority.map_or(u32::MAX, |p| p as u32),
+            i.due.unwrap_or(NaiveDate::MAX),
+        )
+    });
+    Ok(items)
+}
+
+/// Draws up to `lines` items, one per line, with an empty checkbox in front.
+pub fn draw_todo(
+    display: &mut Display,
+    fonts: &Fonts,
+    locale: &Locale,
+    items: &[Item],
+    now: DateTime<FixedOffset>,
+    origin: Coord,
+    lines: usize,
+) {
+    let today = now.date_naive();
+    for (i, item) in items.iter().take(lines).enumerate() {
+        let y = origin[1] + i as i32 * fonts.body.height() as i32;
+        display.draw(
+            Rectangle::new(
+                Coord::new(origin[0], y + 2),
+                Coord::new(origin[0] + 6, y + 8),
+            )
+            .stroke(Some(Color::Black)),
+        );
+        let mut line = String::new();
+        if let Some(priority) = item.priority {
+            line.push_str(&format!("({}) ", priority));
+        }
+        line.push_str(&item.text);
+        let due = item.due.map(|due| match (due - today).num_days() {
+            d if d < 0 => "overdue".to_string(),
+            0 => "today".to_string(),
+            1 => "tomorrow".to_string(),
+            2..=6 => locale.format_date(due, "%a"),
+            _ => locale.format_date(due, "%d/%m"),
+        });
+        // the text gets whatever the checkbox and due label leave
+        let right = display.clip().bottom_right[0] as i32 + 1;
+        let due_x = right - due.as_deref().map_or(0, |d| fonts.body.width(d) as i32);
+        let room = due_x - fonts.body.width(" ") as i32 - (origin[0] + 10);
+        let line = fonts.body.fit(&line, room.max(0) as u32);
+        fonts.body.draw(
+            display,
+            &line,
+            Coord::new(origin[0] + 10, y),
+            Color::Black,
+            Color::White,
+        );
+        // the due date lines up against the right edge of the area
+        if let Some(due) = due {
+            let color = if item.due.is_some_and(|due| due < today) {
+                Color::Red
+            } else {
+                Color::Black
+            };
+            fonts
+                .body
+                .draw(display, &due, Coord::new(due_x, y), color, Color::White);
+        }
+    }
+}
+
+/// Watches the list for changes and sends a message for each one.
+///
+/// The parent directory is watched rather than the file itself since most
+/// editors save by writing a new file and renaming it over the old one.
+/// That also means the list needn't exist yet.
+pub fn watch(path: &Path) -> notify::Result<UnboundedReceiver<()>> {
+    let name = path.file_name().ok_or(notify::Error::PathNotFound)?;
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    }
+    .canonicalize()?;
+    let path = dir.join(name);
+    let (tx, rx) = unbounded_channel();
+    let (events_tx, events) = channel();
+    let mut watcher = watcher(events_tx, Duration::from_millis(500))?;
+    watcher.watch(&dir, RecursiveMode::NonRecursive)?;
+    thread::spawn(move || {
+        // the watcher stops when dropped, keep it alive on this thread
+        let _watcher = watcher;
+        for event in events {
+            let changed = match event {
+                DebouncedEvent::Create(p)
+                | DebouncedEvent::Write(p)
+                | DebouncedEvent::Remove(p)
+                | DebouncedEvent::Rename(_, p) => p == path,
+                DebouncedEvent::Rescan => true,
+                _ => false,
+            };
+            if changed && tx.send(()).is_err() {
+                break;
+            }
+        }
+    });
+    Ok(rx)
+}
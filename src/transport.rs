@@ -0,0 +1,274 @@
+//! Ways of getting a frame to a panel. Every transport sends the same bytes
+//! `push` writes to the serial port, a big endian length then the frame, so
+//! the firmware only has one format to parse whichever way it is connected.
+//!
+//! The network transports connect for each frame, which suits boards that
+//! sleep between updates and drop idle connections.
+
+use crate::{config::DeviceConfig, device::Serial, push, status::DeviceStatus};
+use hyper::Uri;
+use std::{
+    io::{Read, Write},
+    net::{TcpStream, ToSocketAddrs},
+    time::Duration,
+};
+use tokio::sync::mpsc::UnboundedReceiver;
+
+type Error = Box<dyn std::error::Error + Send + Sync>;
+
+/// Long enough for a board to wake its radio, short enough that a dead one
+/// doesn't hold up the others.
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+pub trait Transport: Send {
+    fn send(&mut self, frame: &[u8]) -> Result<(), Error>;
+
+    /// What the board has reported back, for transports that can hear it.
+    fn status(&self) -> Option<DeviceStatus> {
+        None
+    }
+
+    /// Button presses from the board, by number. Can only be taken once.
+    fn buttons(&mut self) -> Option<UnboundedReceiver<u8>> {
+        None
+    }
+}
+
+/// Picks the transport for `target`, given on the command line as
+/// `tcp://host:port`, an `http://` URL or a serial port path, falling back
+/// to the config.
+pub fn open(target: Option<&str>, config: &DeviceConfig) -> Result<Box<dyn Transport>, Error> {
+    if let Some(target) = target {
+        return Ok(if let Some(address) = target.strip_prefix("tcp://") {
+            Box::new(Tcp::new(address))
+        } else if target.contains("://") {
+            Box::new(Http::new(target)?)
+        } else {
+            Box::new(Serial::new(Some(target), config))
+        });
+    }
+    Ok(match (&config.tcp, &config.url) {
+        (Some(address), _) => Box::new(Tcp::new(address)),
+        (None, Some(url)) => Box::new(Http::new(url)?),
+        (None, None) => Box::new(Serial::new(None, config)),
+    })
+}
+
+fn connect(address: &str) -> Result<TcpStream, Error> {
+    let mut last = None;
+    for addr in address.to_socket_addrs()? {
+        match TcpStream::connect_timeout(&addr, TIMEOUT) {
+            Ok(stream) => {
+                stream.set_read_timeout(Some(TIMEOUT))?;
+                stream.set_write_timeout(Some(TIMEOUT))?;
+                return Ok(stream);
+            }
+            Err(e) => last = Some(e),
+        }
+    }
+    Err(match last {
+        Some(e) => format!("unable to connect to {}: {}", address, e).into(),
+        None => format!("{} did not resolve to any address", address).into(),
+    })
+}
+
+/// A board listening on a plain TCP socket, the serial framing over Wi-Fi.
+pub struct Tcp {
+    address: String,
+}
+
+impl Tcp {
+    pub fn new(address: &str) -> Self {
+        Tcp {
+            address: address.to_string(),
+        }
+    }
+}
+
+impl Transport for Tcp {
+    fn send(&mut self, frame: &[u8]) -> Result<(), Error> {
+        let mut stream = connect(&self.address)?;
+        push(&mut stream, frame)?;
+        stream.flush()?;
+        Ok(())
+    }
+}
+
+/// A board running a web server, sent the framed buffer as the body of a
+/// POST. Only plain HTTP, the firmware has no room for TLS.
+pub struct Http {
+    address: String,
+    host: String,
+    path: String,
+}
+
+impl Http {
+    pub fn new(url: &str) -> Result<Self, Error> {
+        let uri: Uri = url.parse()?;
+        if uri.scheme_str() != Some("http") {
+            return Err(format!("{} is not an http:// URL", url).into());
+        }
+        let host = uri
+            .host()
+            .ok_or_else(|| format!("{} has no host", url))?
+            .to_string();
+        let port = uri.port_u16().unwrap_or(80);
+        Ok(Http {
+            address: format!("{}:{}", host, port),
+            host: uri.authority().map_or(host, |a| a.to_string()),
+            path: uri.path_and_query().map_or("/", |p| p.as_str()).to_string(),
+        })
+    }
+}
+
+impl Transport for Http {
+    fn send(&mut self, frame: &[u8]) -> Result<(), Error> {
+        let mut body = Vec::with_capacity(frame.len() + 4);
+        push(&mut body, frame)?;
+
+        let mut stream = connect(&self.address)?;
+        write!(
+            stream,
+            "POST {} HTTP/1.1\r\nHost: {}\r\nContent-Type: application/octet-stream\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+            self.path,
+            self.host,
+            body.len()
+        )?;
+        stream.write_all(&body)?;
+        stream.flush()?;
+
+        // only the status line matters, the rest is left unread
+        let mut response = Vec::new();
+        let mut byte = [0u8];
+        while !response.ends_with(b"\r\n") && stream.read(&mut byte)? == 1 {
+            response.push(byte[0]);
+        }
+        let status = String::from_utf8_lossy(&response);
+        match status.split_whitespace().nth(1) {
+            Some(code) if code.starts_with('2') => Ok(()),
+            _ => Err(format!("{} answered {:?}", self.host, status.trim()).into()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use byteorder::{BigEndian, ReadBytesExt};
+    use std::{
+        io::{BufRead, BufReader},
+        net::TcpListener,
+        thread,
+    };
+
+    /// Reads one length prefixed frame the way the firmware does.
+    fn read_frame(reader: &mut impl Read) -> Vec<u8> {
+        let len = reader.read_u32::<BigEndian>().unwrap();
+        let mut frame = vec![0; len as usize];
+        reader.read_exact(&mut frame).unwrap();
+        frame
+    }
+
+    /// A device stand-in that accepts `count` connections and hands back
+    /// what each sent.
+    fn tcp_device(count: usize) -> (String, thread::JoinHandle<Vec<Vec<u8>>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        let handle = thread::spawn(move || {
+            (0..count)
+                .map(|_| read_frame(&mut listener.accept().unwrap().0))
+                .collect()
+        });
+        (address, handle)
+    }
+
+    /// Request head lines and the frame in the body.
+    type Request = (Vec<String>, Vec<u8>);
+
+    /// An HTTP device stand-in answering one request with `status`,
+    /// handing back the request head and the frame in its body.
+    fn http_device(status: &'static str) -> (String, thread::JoinHandle<Request>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        let handle = thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(stream);
+            let mut head = Vec::new();
+            loop {
+                let mut line = String::new();
+                reader.read_line(&mut line).unwrap();
+                if line == "\r\n" {
+                    break;
+                }
+                head.push(line.trim_end().to_string());
+            }
+            let frame = read_frame(&mut reader);
+            write!(
+                reader.get_mut(),
+                "HTTP/1.1 {}\r\nContent-Length: 0\r\n\r\n",
+                status
+            )
+            .unwrap();
+            (head, frame)
+        });
+        (address, handle)
+    }
+
+    #[test]
+    fn tcp_sends_each_frame_framed() {
+        let (address, device) = tcp_device(2);
+        let mut tcp = Tcp::new(&address);
+        tcp.send(&[0xff; 4000]).unwrap();
+        tcp.send(&[0x00, 0x01, 0x02]).unwrap();
+        let frames = device.join().unwrap();
+        assert_eq!(frames, vec![vec![0xff; 4000], vec![0x00, 0x01, 0x02]]);
+    }
+
+    #[test]
+    fn tcp_without_a_listener_is_an_error() {
+        // bind then drop to get a port nothing is listening on
+        let address = TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .to_string();
+        assert!(Tcp::new(&address).send(&[0]).is_err());
+    }
+
+    #[test]
+    fn http_posts_the_framed_buffer() {
+        let (address, device) = http_device("200 OK");
+        let url = format!("http://{}/frame?panel=hall", address);
+        Http::new(&url).unwrap().send(&[0xaa; 16]).unwrap();
+        let (head, frame) = device.join().unwrap();
+        assert_eq!(head[0], "POST /frame?panel=hall HTTP/1.1");
+        assert!(head.contains(&format!("Host: {}", address)));
+        assert!(head.contains(&"Content-Length: 20".to_string()));
+        assert_eq!(frame, vec![0xaa; 16]);
+    }
+
+    #[test]
+    fn http_error_status_is_an_error() {
+        let (address, device) = http_device("507 Insufficient Storage");
+        let err = Http::new(&format!("http://{}/", address))
+            .unwrap()
+            .send(&[0; 8])
+            .unwrap_err();
+        assert!(err.to_string().contains("507"), "{}", err);
+        device.join().unwrap();
+    }
+
+    #[test]
+    fn picks_the_transport_from_the_target() {
+        let config = DeviceConfig::default();
+        assert!(open(Some("/dev/ttyACM0"), &config).is_ok());
+        assert!(open(Some("tcp://192.168.1.50:8080"), &config).is_ok());
+        assert!(open(Some("http://192.168.1.50/frame"), &config).is_ok());
+        assert!(open(Some("https://example.com/"), &config).is_err());
+        let config = DeviceConfig {
+            url: Some("ftp://example.com/".to_string()),
+            ..DeviceConfig::default()
+        };
+        assert!(open(None, &config).is_err());
+    }
+}
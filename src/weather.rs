@@ -0,0 +1,565 @@
+//! Dark Sky forecast data and the weather panel.
+
+use crate::{fonts::Fonts, icons::Icons, locale::Locale, Color, Display, HttpsClient};
+use bytes::buf::BufExt as _;
+use chrono::{DateTime, TimeZone};
+use chrono_tz::Tz;
+use dotenv_codegen::dotenv;
+use embedded_graphics::prelude::*;
+use serde_derive::{Deserialize, Serialize};
+use textwrap::fill;
+
+pub async fn fetch(
+    client: &HttpsClient,
+) -> Result<Forecast, Box<dyn std::error::Error + Send + Sync>> {
+    let token = dotenv!("API_KEY");
+    let lat: f32 = 31.1171;
+    let long: f32 = -97.7278;
+
+    let weather_uri = format!(
+        "https://api.darksky.net/forecast/{}/{},{}",
+        token, lat, long
+    )
+    .parse()?;
+    let weather_resp = client.get(weather_uri).await?;
+    let weather_body = hyper::body::aggregate(weather_resp).await?;
+
+    // try to parse as json with serde_json
+    Ok(serde_json::from_reader(weather_body.reader())?)
+}
+
+/// The icon drawn for a datapoint, for the conditions there is one for.
+pub fn icon_name(icon: Option<&Icon>) -> Option<&'static str> {
+    match icon {
+        Some(Icon::ClearDay) => Some("clearday"),
+        Some(Icon::ClearNight) => Some("clearnight"),
+        Some(Icon::Cloudy) => Some("cloudy"),
+        Some(Icon::PartlyCloudyDay) => Some("partlycloudyday"),
+        _ => None,
+    }
+}
+
+/// Draws the current conditions and the today/tomorrow summaries on the
+/// right hand side, plus the condition icon and wind arrow. The day's
+/// sunrise and sunset take the last line of the area.
+pub fn draw_weather(
+    display: &mut Display,
+    fonts: &Fonts,
+    locale: &Locale,
+    forecast: &Forecast,
+    icons: &Icons,
+) {
+    // the icons down the left of the area, everything else beside them
+    let area = display.clip();
+    let left = area.top_left[0] as i32;
+    let column = left + 44;
+    let right = area.bottom_right[0] as i32 + 1;
+    let columns = fonts.body.columns((right - column).max(0) as u32);
+    if let Some(currently) = &forecast.currently {
+        if let Some(temp) = currently.temperature {
+            let temp = format!("{:2.0}°", temp);
+            fonts.title.draw(
+                display,
+                &temp,
+                Coord::new(column, 0),
+                Color::Black,
+                Color::White,
+            );
+        }
+        if let Some(precip) = currently.precip_probability {
+            let precip = format!("{:2.0}%", precip);
+            fonts.title.draw(
+                display,
+                &precip,
+                Coord::new(column + fonts.title.width("00°") as i32 + 2, 0),
+                Color::Black,
+                Color::White,
+            );
+        }
+        if let Some(wind) = currently.wind_speed {
+            if let Some(dir) = currently.wind_bearing {
+                let name = match dir as i32 {
+                    337..360 | 0..22 => "arrow_north",
+                    22..67 => "arrow_north_east",
+                    67..112 => "arrow_east",
+                    112..157 => "arrow_south_east",
+                    157..202 => "arrow_south",
+                    202..247 => "arrow_south_west",
+                    247..292 => "arrow_west",
+                    292..337 => "arrow_north_west",
+                    _ => "arrow_north",
+                };
+                icons.draw(display, name, Coord::new(left, 44));
+            }
+            let wind = format!("{:2.0}MPH", wind);
+            fonts.title.draw(
+                display,
+                &wind,
+                Coord::new(right - fonts.title.width(&wind) as i32, 0),
+                Color::Black,
+                Color::White,
+            );
+        }
+        // the summaries one body line apart, below the title row
+        let mut rows = Vec::new();
+        if let Some(summary) = &currently.summary {
+            rows.push(format!("Currently: {}", summary));
+        }
+        if let Some(data) = forecast.daily.as_ref().and_then(|d| d.data.as_ref()) {
+            for (label, day) in ["Today", "Tomorrow"].iter().zip(data) {
+                if let Some(summary) = &day.summary {
+                    rows.push(format!("{}: {}", label, summary));
+                }
+            }
+        }
+        let line_height = fonts.body.height() as i32;
+        let mut bottom = area.bottom_right[1] as i32 + 1;
+        // the day's sunrise and sunset, in the forecast location's zone
+        let sun = |t: Option<u64>| {
+            t.and_then(|t| forecast.local_time(t))
+                .map(|t| locale.time(&t))
+        };
+        let today = forecast
+            .daily
+            .as_ref()
+            .and_then(|d| d.data.as_ref())
+            .and_then(|data| data.first());
+        if let Some((Some(rise), Some(set))) =
+            today.map(|day| (sun(day.sunrise_time), sun(day.sunset_time)))
+        {
+            bottom -= line_height;
+            fonts.body.draw(
+                display,
+                &fonts.body.fit(
+                    &format!("rise {} set {}", rise, set),
+                    (right - column).max(0) as u32,
+                ),
+                Coord::new(column, bottom),
+                Color::Black,
+                Color::White,
+            );
+        }
+        let fits = ((bottom - 20) / line_height.max(1)).max(0) as usize;
+        let lines = rows.iter().flat_map(|row| {
+            fill(row, columns)
+                .split('\n')
+                .map(String::from)
+                .collect::<Vec<_>>()
+        });
+        for (i, line) in lines.take(fits).enumerate() {
+            fonts.body.draw(
+                display,
+                &line,
+                Coord::new(column, 20 + i as i32 * line_height),
+                Color::Black,
+                Color::White,
+            );
+        }
+        if let Some(name) = icon_name(currently.icon.as_ref()) {
+            icons.draw(display, name, Coord::new(left, 0));
+        }
+    }
+}
+
+#[derive(Deserialize, Debug)]
+#[allow(dead_code)]
+pub struct Forecast {
+    latitude: f32,
+    longitude: f32,
+    timezone: String,
+    pub currently: Option<Datapoint>,
+    pub daily: Option<Datablock>,
+    pub hourly: Option<Datablock>,
+    pub minutely: Option<Datablock>,
+    pub flags: Option<Flags>,
+}
+
+impl Forecast {
+    /// A Dark Sky timestamp in the forecast's own `timezone`, which need not
+    /// be the host's.
+    pub fn local_time(&self, timestamp: u64) -> Option<DateTime<Tz>> {
+        let tz: Tz = self.timezone.parse().ok()?;
+        tz.timestamp_opt(timestamp as i64, 0).single()
+    }
+}
+
+/// A datapoint within a [`Datablock`], where there is usually multiple.
+///
+/// All fields are optional _except for [`time`]_, as some data may not be
+/// available for a location at a given point in time.
+///
+/// All of the data oriented fields may have associated `error` fields,
+/// representing the confidence in a prediction or value. An example is
+/// [`precip_accumulation`], which has an associated error field of
+/// [`precip_accumulation_error`]. Those fields represent standard deviations of
+/// the value of the associated field. Smaller error values represent greater
+/// confidence levels, while larger error values represent less confidence.
+/// These fields are omitted where the confidence is not precisely known.
+///
+/// [`Datablock`]: struct.Datablock.html
+/// [`time`]: #structfield.time
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Datapoint {
+    /// The unix timestamp representing when the daytime high apparent
+    /// temperature occurs.
+    ///
+    /// **Note**: This is only present on the `daily` block.
+    pub apparent_temperature_max_time: Option<u64>,
+    /// The daytime high apparent temperature.
+    ///
+    /// **Note**: This is only present on the `daily` block.
+    pub apparent_temperature_max: Option<f64>,
+    /// The unix timestamp representing when the overnight low apparent
+    /// temperature occurs.
+    ///
+    /// **Note**: This is only present on the `daily` block.
+    pub apparent_temperature_min_time: Option<u64>,
+    /// The overnight low apparent temperature.
+    ///
+    /// **Note**: This is only present on the `daily` block.
+    pub apparent_temperature_min: Option<f64>,
+    /// The apparent (or "feels like") temperature in degrees Fahrenheit.
+    ///
+    /// **Note**: This is not present on `daily`.
+    pub apparent_temperature: Option<f64>,
+    /// The amount of error possible within the [`cloud_cover`] value.
+    ///
+    /// [`cloud_cover`]: #structfield.cloud_cover
+    pub cloud_cover_error: Option<f64>,
+    /// The percentage of sky occluded by clouds.
+    ///
+    /// This value is between `0` and `1`, inclusively.
+    pub cloud_cover: Option<f64>,
+    /// The amount of error possible within the [`dew_point`] value.
+    ///
+    /// [`dew_point`]: #structfield.dew_point
+    pub dew_point_error: Option<f64>,
+    /// The dew point in degrees Fahrenheit.
+    pub dew_point: Option<f64>,
+    /// The amount of error possible within the [`humidity`] value.
+    ///
+    /// [`humidity`]: #structfield.humidity
+    pub humidity_error: Option<f64>,
+    /// The relative humidity.
+    ///
+    /// This value is between `0` and `1`, inclusively.
+    pub humidity: Option<f64>,
+    /// A machine-readable summary of the datapoint, suitable for selecting an
+    /// icon to display.
+    pub icon: Option<Icon>,
+    /// The fractional part of the [lunation number] during the given day.
+    ///
+    /// A value of `0` corresponds to a new moon, `0.25` to a first quarter
+    /// moon, `0.5` to a full moon, `0.75` to a last quarter moon.
+    ///
+    /// **Note**: This is only present on the `daily` block.
+    pub moon_phase: Option<f64>,
+    /// The approximate direction of the nearest storm in degrees, with true
+    /// north at 0 degrees and progressing clockwise.
+    ///
+    /// If `nearestStormDistance` is `0`, then this value will not be present.
+    ///
+    /// **Note**: This is only present on the `currently` block.
+    pub nearest_storm_bearing: Option<f64>,
+    /// The approximate distance to the nearest storm in miles.
+    ///
+    /// A storm distance of `0` doesn't necessarily refer to a storm at the
+    /// requested location, but rather a storm in the vicinity of that location.
+    ///
+    /// **Note**: This is only present on the `currently` block.
+    pub nearest_storm_distance: Option<f64>,
+    /// The amount of error possible within the [`ozone`] value.
+    ///
+    /// [`ozone`]: #structfield.ozone
+    pub ozone_error: Option<f64>,
+    /// The columnar density of total atmospheric ozone at the given time in
+    /// Dobson units.
+    pub ozone: Option<f64>,
+    /// The amount of error possible within the [`precip_accumulation`] value.
+    ///
+    /// [`precip_accumulation`]: #structfield.precip_accumulation
+    pub precip_accumulation_error: Option<f64>,
+    /// The amount of snowfall accumulation expected to occur, in inches.
+    ///
+    /// If no snowfall is expected, this will be None.
+    ///
+    /// **Note**: This is only present on `hourly` and `daily` blocks.
+    pub precip_accumulation: Option<f64>,
+    /// The amount of error possible within the [`precip_intensity`] value.
+    ///
+    /// [`precip_intensity`]: #structfield.precip_intensity
+    pub precip_intensity_error: Option<f64>,
+    /// The amount of error possible within the [`precip_intensity_max`] value.
+    ///
+    /// [`precip_intensity_max`]: #structfield.precip_intensity_max
+    pub precip_intensity_max_error: Option<f64>,
+    /// The unix timestamp of when [`precip_intensity_max`] occurs during a
+    /// given day.
+    ///
+    /// **Note**: This is only present on the `daily` block.
+    ///
+    /// [`precip_intensity_max`]: #structfield.precip_intensity_max
+    pub precip_intensity_max_time: Option<u64>,
+    /// The maximum value of [`precip_intensity`] during a given day.
+    ///
+    /// **Note**: This is only present on the `daily` block.
+    ///
+    /// [`precip_intensity`]: #structfield.precip_intensity
+    pub precip_intensity_max: Option<f64>,
+    /// The intensity (in inches of liquid water per hour) precipitation
+    /// occurring at the given time.
+    ///
+    /// This value is conditional on probability (that is, assuming any
+    /// precipitation occurs at all) for `minutely` datapoints, and
+    /// unconditional otherwise.
+    pub precip_intensity: Option<f64>,
+    /// The amount of error possible within the [`precip_probability`] value.
+    ///
+    /// [`precip_probability`]: #structfield.precip_probability
+    pub precip_probability_error: Option<f64>,
+    /// The probably of precipitation occurring.
+    ///
+    /// This value is between `0` and `1`, inclusively.
+    pub precip_probability: Option<f64>,
+    /// The type of precipitation occurring at a given time.
+    ///
+    /// If [`precip_intensity`] is `0`, then this field will be `None`.
+    ///
+    /// Additionally, due to the lack of data in DarkSky sources, historical
+    /// `precip_type` values is usually estimated, rather than observed.
+    ///
+    /// [`precip_intensity`]: #structfield.precip_intensity
+    pub precip_type: Option<PrecipitationType>,
+    /// The amount of error possible within the [`pressure`] value.
+    ///
+    /// [`pressure`]: #structfield.pressure
+    pub pressure_error: Option<f64>,
+    /// The sea-level air pressure in millibars.
+    pub pressure: Option<f64>,
+    /// A human-readable text summary of the datapoint.
+    ///
+    /// **Note**: Do not use this for automated icon display purposes, use the
+    /// [`icon`] field instead.
+    ///
+    /// [`icon`]: #structfield.icon
+    pub summary: Option<String>,
+    /// The unix timestamp of when the sun will rise during a given day.
+    ///
+    /// **Note**: This is only present on the `daily` block.
+    pub sunrise_time: Option<u64>,
+    /// The unix timestamp of when the sun will set during a given day.
+    ///
+    /// **Note**: This is only present on the `daily` block.
+    pub sunset_time: Option<u64>,
+    /// The overnight low temperature.
+    ///
+    /// **Note**: This is only present on the `daily` block.
+    pub temperature_low: Option<f64>,
+    /// The unix timestamp representing when the overnight low temperature
+    /// occurs.
+    ///
+    /// **Note**: This is only present on the `daily` block.
+    pub temperature_low_time: Option<u64>,
+    /// The daytime high temperature.
+    ///
+    /// **Note**: This is only present on the `daily` block.
+    pub temperature_high: Option<f64>,
+    /// The unix timestamp representing when the daytime high temperature
+    /// occurs.
+    ///
+    /// **Note**: This is only present on the `daily` block.
+    pub temperature_high_time: Option<u64>,
+    /// The amount of error possible within the [`temperature_max`] value.
+    ///
+    /// [`temperature_max`]: #structfield.temperature_max
+    pub temperature_max_error: Option<f64>,
+    /// The unix timestamp representing when the maximum temperature during a
+    /// given date occurs.
+    ///
+    /// **Note**: This is only present on the `daily` block.
+    pub temperature_max_time: Option<u64>,
+    /// The maximum temperature during a given date.
+    ///
+    /// **Note**: This is only present on the `daily` block.
+    pub temperature_max: Option<f64>,
+    /// The amount of error possible within the [`temperature_min`] value.
+    ///
+    /// [`temperature_min`]: #structfield.temperature_min
+    pub temperature_min_error: Option<f64>,
+    /// The unix timestamp representing when the minimum temperature during a
+    /// given date occurs.
+    ///
+    /// **Note**: This is only present on the `daily` block.
+    pub temperature_min_time: Option<u64>,
+    /// The minimum temperature during a given date.
+    ///
+    /// **Note**: This is only present on the `daily` block.
+    pub temperature_min: Option<f64>,
+    /// The amount of error possible within the [`temperature`] value.
+    ///
+    /// [`temperature`]: #structfield.temperature
+    pub temperature_error: Option<f64>,
+    /// The air temperature in degrees Fahrenheit.
+    pub temperature: Option<f64>,
+    /// The unix timestamp at which the datapoint begins.
+    ///
+    /// `minutely` datapoints are always aligned to the top of the minute.
+    ///
+    /// `hourly` datapoints align to the top of the hour.
+    ///
+    /// `daily` datapoints align to midnight of the day.
+    ///
+    /// All are according to the local timezone.
+    pub time: u64,
+    /// The UV index.
+    pub uv_index: Option<u64>,
+    /// The unix timestamp of when the maximum [`uv_index`] occurs during the
+    /// given day.
+    ///
+    /// [`uv_index`]: #structfield.uv_index
+    pub uv_index_time: Option<u64>,
+    /// The amount of error possible within the [`visibility`] value.
+    ///
+    /// [`visibility`]: #structfield.visibility
+    pub visibility_error: Option<f64>,
+    /// The average visibility in miles, capped at 10 miles.
+    pub visibility: Option<f64>,
+    /// The amount of error possible within the [`wind_bearing`] value.
+    ///
+    /// [`wind_bearing`]: #structfield.wind_bearing
+    pub wind_bearing_error: Option<f64>,
+    /// The direction that the wind is coming from in degrees.
+    ///
+    /// True north is at 0 degrees, progressing clockwise.
+    ///
+    /// If [`wind_speed`] is `0`, then this value will not be defined.
+    ///
+    /// [`wind_speed`]: #structfield.wind_speed
+    pub wind_bearing: Option<f64>,
+    /// The wind gust speed in miles per hour.
+    pub wind_gust: Option<f64>,
+    /// The amount of time that the wind gust is expected to occur.
+    pub wind_gust_time: Option<u64>,
+    /// The amount of error possible within the [`wind_speed`] value.
+    ///
+    /// [`wind_speed`]: #structfield.wind_speed
+    pub wind_speed_error: Option<f64>,
+    /// The wind speed in miles per hour.
+    pub wind_speed: Option<f64>,
+}
+
+/// The type of precipitation that is happening within a [`Datapoint`].
+///
+/// [`Datapoint`]: struct.Datapoint.html
+#[derive(Copy, Clone, Debug, Deserialize, Eq, Hash, PartialEq, PartialOrd, Ord, Serialize)]
+pub enum PrecipitationType {
+    /// Indicator that the type of precipitation is rain.
+    #[serde(rename = "rain")]
+    Rain,
+    /// Indicator that the type of precipitation is sleet.
+    #[serde(rename = "sleet")]
+    Sleet,
+    /// Indicator that the type of precipitation is snow.
+    #[serde(rename = "snow")]
+    Snow,
+}
+
+/// A safe representation of the indicated weather. This is useful for matching
+/// and presenting an emoji or other weather symbol or representation.
+#[derive(Copy, Clone, Debug, Deserialize, Eq, Hash, PartialEq, PartialOrd, Ord, Serialize)]
+pub enum Icon {
+    /// The day's sky is clear.
+    #[serde(rename = "clear-day")]
+    ClearDay,
+    /// The night sky is clear.
+    #[serde(rename = "clear-night")]
+    ClearNight,
+    /// The sky is cloudy.
+    #[serde(rename = "cloudy")]
+    Cloudy,
+    /// It is foggy.
+    #[serde(rename = "fog")]
+    Fog,
+    /// Not actively in use
+    #[serde(rename = "hail")]
+    Hail,
+    /// The day's sky is partly cloudy.
+    #[serde(rename = "partly-cloudy-day")]
+    PartlyCloudyDay,
+    /// The night's sky is partly night.
+    #[serde(rename = "partly-cloudy-night")]
+    PartlyCloudyNight,
+    /// The weather is rain.
+    #[serde(rename = "rain")]
+    Rain,
+    /// The weather is sleet.
+    #[serde(rename = "sleet")]
+    Sleet,
+    /// The weather is snow.
+    #[serde(rename = "snow")]
+    Snow,
+    /// Not actively in use
+    #[serde(rename = "thunderstorm")]
+    Thunderstorm,
+    /// Not actively in use
+    #[serde(rename = "tornado")]
+    Tornado,
+    /// The weather is windy.
+    #[serde(rename = "wind")]
+    Wind,
+}
+
+/// A set of flags for a forecast, such as the [`Unit`]s specified or the vector
+/// of [DarkSky] stations reporting.
+///
+/// [`Unit`]: enum.Unit.html
+/// [DarkSky]: https://darksky.net
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct Flags {
+    /// A list of DarkSky stations used for the [`Forecast`].
+    ///
+    /// [`Forecast`]: struct.Forecast.html
+    pub darksky_stations: Option<Vec<String>>,
+    /// A list of the unavailable DarkSky stations.
+    pub darksky_unavailable: Option<String>,
+    /// A list of the
+    pub datapoint_stations: Option<Vec<String>>,
+    /// A list of [ISD] stations used.
+    ///
+    /// [ISD]: https://www.ncdc.noaa.gov/isd
+    pub isd_stations: Option<Vec<String>>,
+    /// A list of [LAMP] stations used to obtain the information.
+    ///
+    /// [LAMP]: http://www.nws.noaa.gov/mdl/lamp/lamp_info.shtml
+    pub lamp_stations: Option<Vec<String>>,
+    /// A list of [METAR] stations used to obtain the information.
+    ///
+    /// [METAR]: https://www.aviationweather.gov/metar
+    pub metar_stations: Option<Vec<String>>,
+    /// The [METNO license] used.
+    ///
+    /// [METNO license]: http://www.met.no/
+    pub metno_license: Option<String>,
+    /// A list of sources used to obtain the information.
+    pub sources: Option<Vec<String>>,
+    /// The [`Unit`]s used to format the data.
+    ///
+    /// [`Unit`]: enum.Unit.html
+    pub units: Option<String>,
+}
+
+/// A block of data within a [`Forecast`], with potentially many [`Datapoint`]s.
+///
+/// [`Datapoint`]: struct.Datapoint.html
+/// [`Forecast`]: struct.Forecast.html
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Datablock {
+    /// The data for the datablock, if there is any data available.
+    pub data: Option<Vec<Datapoint>>,
+    /// The icon representing the weather type for the datablock.
+    pub icon: Option<Icon>,
+    /// A written summary of the datablock's expected weather.
+    pub summary: Option<String>,
+}
@@ -0,0 +1,52 @@
+//! The time in other zones, as `NYC 09:14 / LON 14:14 / BLR 18:44+1`.
+
+use crate::{config::WorldClockConfig, display::Clip, fonts::Face, locale::Locale, Color, Display};
+use chrono::{DateTime, FixedOffset};
+use embedded_graphics::prelude::*;
+
+/// One entry per zone, marked `+1` or `-1` when the date there differs
+/// from the date here.
+fn entries(config: &WorldClockConfig, locale: &Locale, now: DateTime<FixedOffset>) -> Vec<String> {
+    config
+        .zones
+        .iter()
+        .map(|zone| {
+            let there = now.with_timezone(&zone.timezone);
+            let days = (there.date_naive() - now.date_naive()).num_days();
+            let marker = match days {
+                0 => String::new(),
+                d => format!("{:+}", d),
+            };
+            format!("{} {}{}", zone.label, locale.time(&there), marker)
+        })
+        .collect()
+}
+
+/// Lays the zones out in `face`, separated by slashes and wrapped to fill
+/// as many whole lines of `area` as needed.
+pub fn draw_worldclock(
+    display: &mut Display,
+    face: &Face,
+    locale: &Locale,
+    config: &WorldClockConfig,
+    now: DateTime<FixedOffset>,
+    area: Clip,
+) {
+    let origin = area.top_left.to_signed();
+    let width = area.bottom_right[0] - area.top_left[0] + 1;
+    let lines = ((area.bottom_right[1] - area.top_left[1] + 1) / face.height().max(1)) as usize;
+    let mut rows: Vec<String> = Vec::new();
+    for entry in entries(config, locale, now) {
+        match rows.last_mut() {
+            Some(row) if face.width(&format!("{} / {}", row, entry)) <= width => {
+                row.push_str(" / ");
+                row.push_str(&entry);
+            }
+            _ => rows.push(entry),
+        }
+    }
+    for (i, row) in rows.iter().take(lines).enumerate() {
+        let at = origin + Coord::new(0, (i as u32 * face.height()) as i32);
+        face.draw(display, row, at, Color::Black, Color::White);
+    }
+}